@@ -1,3 +1,9 @@
-create unique index idx_page_titles on PAGES(page_title);
-create index idx_links_page_id on LINKS(page_id);
-create index idx_redirects_og_page_titles on REDIRECTS(page_title);
+create unique index if not exists idx_page_titles on PAGES(page_title);
+create index if not exists idx_links_page_id on LINKS(page_id);
+create index if not exists idx_redirects_og_page_titles on REDIRECTS(page_title);
+create index if not exists idx_links_link_titles on LINKS(link_title);
+create index if not exists idx_redirects_redirect_titles on REDIRECTS(redirect_title);
+create index if not exists idx_category_members_page_id on CATEGORY_MEMBERS(page_id);
+create index if not exists idx_category_members_categories on CATEGORY_MEMBERS(category);
+create index if not exists idx_subcategories_category_id on SUBCATEGORIES(category_id);
+create index if not exists idx_subcategories_parents on SUBCATEGORIES(parent);
@@ -0,0 +1,15 @@
+-- Fills RED_LINKS from LINKS, PAGES and REDIRECTS, needs the indexes from create_indexes.sql to run in reasonable time.
+-- A link is red when its title is neither a page nor a redirect to one, searches skip those links. pages counts the
+-- distinct pages linking to the title. Running it again rebuilds RED_LINKS
+delete from RED_LINKS;
+drop index if exists idx_red_links_pages;
+
+insert into RED_LINKS(title, pages)
+  select l.link_title, count(distinct l.page_id) from LINKS l
+  where not exists (select 1 from PAGES p where p.page_title = l.link_title)
+    and not exists (
+      select 1 from REDIRECTS r join PAGES p on p.page_title = r.redirect_title where r.page_title = l.link_title
+    )
+  group by l.link_title;
+
+create index idx_red_links_pages on RED_LINKS(pages desc, title);
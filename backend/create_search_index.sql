@@ -0,0 +1,11 @@
+-- Full text index over titles and descriptions used by /search, built after seeding unless
+-- WIKIMAPIA_SEARCH_INDEX is set to 0. It's an external content table, the text itself stays in PAGES
+drop table if exists PAGE_SEARCH;
+create virtual table PAGE_SEARCH using fts5(
+  page_title,
+  description,
+  content = 'PAGES',
+  content_rowid = 'id',
+  tokenize = 'porter unicode61'
+);
+insert into PAGE_SEARCH(PAGE_SEARCH) values('rebuild');
@@ -0,0 +1,25 @@
+-- Fills SUGGESTIONS from PAGES and REDIRECTS, needs the indexes from create_indexes.sql to run in reasonable time.
+-- A page's in_degree counts the pages linking to it and to every redirect pointing at it.
+-- Running it again rebuilds SUGGESTIONS
+delete from SUGGESTIONS;
+drop index if exists idx_suggestions_titles;
+
+-- Every page linking to a title, directly or through a redirect to it. A page linking more than once counts once
+create temp table IN_DEGREES as
+  select title, count(distinct page_id) as in_degree from (
+    select link_title as title, page_id from LINKS
+    union all
+    select r.redirect_title, l.page_id from REDIRECTS r join LINKS l on l.link_title = r.page_title
+  ) group by title;
+create index temp.idx_in_degrees_titles on IN_DEGREES(title);
+
+insert into SUGGESTIONS(title, redirect_to, in_degree)
+  select p.page_title, null, coalesce(d.in_degree, 0) from PAGES p left join IN_DEGREES d on d.title = p.page_title;
+insert into SUGGESTIONS(title, redirect_to, in_degree)
+  select r.page_title, r.redirect_title, coalesce(d.in_degree, 0) from REDIRECTS r
+    join PAGES p on p.page_title = r.redirect_title
+    left join IN_DEGREES d on d.title = r.redirect_title;
+
+create index idx_suggestions_titles on SUGGESTIONS(title collate nocase);
+
+drop table IN_DEGREES;
@@ -1,16 +1,30 @@
 drop table if exists PAGES;
 drop table if exists LINKS;
 drop table if exists REDIRECTS;
+drop table if exists CATEGORIES;
+drop table if exists CATEGORY_MEMBERS;
+drop table if exists SUBCATEGORIES;
+drop table if exists SUGGESTIONS;
+drop table if exists PAGE_SEARCH;
+drop table if exists RED_LINKS;
+drop table if exists PAGE_STATS;
+drop table if exists SEED_PROGRESS;
 
 create table PAGES (
   id integer not null primary key,
-  page_title text not null unique
+  page_title text not null unique,
+  -- The {{Short description|...}} of the article, or the first sentence of its lead section
+  description text,
+  -- 1 for disambiguation pages, the ones using a {{disambiguation}} family template or titled "... (disambiguation)"
+  disambiguation integer not null default 0
 );
 
 create table LINKS (
   id integer not null primary key,
   page_id integer not null,
   link_title text not null,
+  -- How the link was written, 'wikilink' for [[...]] links and 'etymology' for {{etymology|<language code>}} templates
+  edge_type text not null default 'wikilink',
   foreign key (page_id) references PAGES(id)
 );
 
@@ -19,3 +33,57 @@ create table REDIRECTS (
   page_title text not null,
   redirect_title text not null
 );
+
+-- Category pages, namespace 14 of the dump. Titles are stored without the "Category:" prefix. hidden is 1 for
+-- maintenance categories marked with __HIDDENCAT__, which Wikipedia doesn't show readers
+create table CATEGORIES (
+  id integer not null primary key,
+  title text not null unique,
+  hidden integer not null default 0
+);
+
+-- Categories an article is in, from its [[Category:...]] links. A category doesn't need a page in CATEGORIES to
+-- have members
+create table CATEGORY_MEMBERS (
+  page_id integer not null,
+  category text not null,
+  foreign key (page_id) references PAGES(id)
+);
+
+-- The category hierarchy, the category with id category_id is a subcategory of parent. Each pair is only stored once,
+-- so a section that's seeded again after being interrupted doesn't repeat the pairs of categories seeded before it
+create table SUBCATEGORIES (
+  category_id integer not null,
+  parent text not null,
+  foreign key (category_id) references CATEGORIES(id)
+);
+create unique index idx_subcategories_pairs on SUBCATEGORIES(category_id, parent);
+
+-- Titles offered by /suggest, filled in by create_suggestions.sql once seeding is done. Redirects are listed with
+-- the page they redirect to in redirect_to and ranked by that page's in_degree
+create table SUGGESTIONS (
+  title text not null collate nocase,
+  redirect_to text,
+  in_degree integer not null
+);
+
+-- Titles linked to that aren't pages or redirects to one, filled in by create_red_links.sql once seeding is done
+create table RED_LINKS (
+  title text not null primary key,
+  pages integer not null
+);
+
+-- PAGE_STATS holds scores computed per page once seeding is done, like pagerank. It's created by the commands that
+-- fill it and dropped above since its page ids don't match a new seed
+
+-- Dump sections that have been seeded, so an interrupted seed can be resumed. first_page_id, first_redirect_id and
+-- first_category_id are the ids the section's rows start at, everything from them on is removed if the section didn't finish.
+-- finished_at is when the section finished, the last of them is when the seed finished
+create table SEED_PROGRESS (
+  section text not null primary key,
+  first_page_id integer not null,
+  first_redirect_id integer not null,
+  first_category_id integer not null default 1,
+  finished integer not null default 0,
+  finished_at text
+);
@@ -0,0 +1,266 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::{self, available_parallelism};
+use std::time::Instant;
+
+use crate::search::{self, Graph, SearchOptions, Unreachable};
+use crate::str_utils;
+
+// Most pairs a single POST /paths request can ask for, the batch command has no limit
+pub const MAX_BATCH_SIZE: usize = 10000;
+
+// Columns of a batch written as csv, in order
+const CSV_HEADER: &str = "startpage,endpage,status,path_length,elapsed_ms,path";
+
+// Separates the pages of a path in the path column of a csv
+const CSV_PATH_SEPARATOR: &str = " -> ";
+
+// A pair of pages to find a path between
+#[derive(Deserialize)]
+pub struct BatchQuery {
+    pub startpage: String,
+    pub endpage: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Ok,
+    NoPath,
+    Error,
+}
+
+impl BatchStatus {
+    // Same as the serialized name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BatchStatus::Ok => "ok",
+            BatchStatus::NoPath => "no_path",
+            BatchStatus::Error => "error",
+        }
+    }
+}
+
+// Result of one pair of a batch, reason is set when there's no path and error when the search failed
+#[derive(Serialize)]
+pub struct BatchResult {
+    pub startpage: String,
+    pub endpage: String,
+    pub status: BatchStatus,
+    pub path: Option<Vec<String>>,
+    pub path_length: Option<usize>,
+    pub elapsed_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<Unreachable>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/* Runs the shortest path searches of a batch with the same options on one thread per core. Every thread opens its
+own Graph and takes the next pair that hasn't been started so a slow search doesn't hold up the others. Searches
+don't go through PATH_CACHE, a batch would only fill it with pairs that aren't asked for again, but titles and
+options are normalized the same way. Results are returned in the order of queries, with the titles as they were given */
+pub fn run_batch(
+    db_path: &str,
+    queries: Vec<BatchQuery>,
+    options: &SearchOptions,
+) -> rusqlite::Result<Vec<BatchResult>> {
+    let num_threads = available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(queries.len().max(1));
+    let graphs = (0..num_threads)
+        .map(|_| Graph::open(db_path))
+        .collect::<rusqlite::Result<Vec<Graph>>>()?;
+    let options = &options.clone().normalized();
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<BatchResult>>> =
+        Mutex::new(queries.iter().map(|_| None).collect());
+    thread::scope(|scope| {
+        for graph in graphs {
+            let (next, results, queries) = (&next, &results, &queries);
+            scope.spawn(move || {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(query) = queries.get(i) else {
+                        break;
+                    };
+                    let result = run_query(&graph, query, options);
+                    results.lock().unwrap()[i] = Some(result);
+                }
+            });
+        }
+    });
+    Ok(results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.unwrap())
+        .collect())
+}
+
+fn run_query(graph: &Graph, query: &BatchQuery, options: &SearchOptions) -> BatchResult {
+    let start = Instant::now();
+    let start_page = str_utils::normalize_title(&query.startpage);
+    let end_page = str_utils::normalize_title(&query.endpage);
+    let outcome = search::find_shortest_path_in(graph, &start_page, &end_page, options, &mut |_| true);
+    let mut result = BatchResult {
+        startpage: query.startpage.clone(),
+        endpage: query.endpage.clone(),
+        status: BatchStatus::Ok,
+        path: None,
+        path_length: None,
+        elapsed_ms: start.elapsed().as_millis(),
+        reason: None,
+        error: None,
+    };
+    match outcome {
+        Ok(Ok(path)) => {
+            result.path_length = Some(path.pages.len());
+            result.path = Some(path.pages);
+        }
+        Ok(Err(Unreachable::PageNotFound { title })) => {
+            result.status = BatchStatus::Error;
+            result.error = Some(format!("Page {} not found", title));
+        }
+        Ok(Err(Unreachable::CategoryNotFound { category })) => {
+            result.status = BatchStatus::Error;
+            result.error = Some(format!("Category {} not found", category));
+        }
+        Ok(Err(reason)) => {
+            result.status = BatchStatus::NoPath;
+            result.reason = Some(reason);
+        }
+        Err(e) => {
+            result.status = BatchStatus::Error;
+            result.error = Some(e.to_string());
+        }
+    }
+    result
+}
+
+/* Reads pairs from csv, one startpage,endpage pair per line. A header line naming those columns is skipped and
+titles containing commas can be quoted */
+pub fn parse_csv(input: &str) -> Result<Vec<BatchQuery>, String> {
+    let mut queries = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = csv_fields(line);
+        if i == 0 && fields.len() >= 2 && fields[0] == "startpage" && fields[1] == "endpage" {
+            continue;
+        }
+        match fields.as_slice() {
+            [startpage, endpage, ..] => queries.push(BatchQuery {
+                startpage: startpage.clone(),
+                endpage: endpage.clone(),
+            }),
+            _ => return Err(format!("Line {} needs a start page and an end page", i + 1)),
+        }
+    }
+    Ok(queries)
+}
+
+// Reads pairs from json lines, each line an object like {"startpage": "Cat", "endpage": "Dog"}
+pub fn parse_jsonl(input: &str) -> Result<Vec<BatchQuery>, String> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| format!("Line {}: {}", i + 1, e)))
+        .collect()
+}
+
+// Splits a csv line into its fields, double quotes in a quoted field are escaped by doubling them
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+// Writes results as csv with a header line, the pages of a path are joined by " -> "
+pub fn to_csv(results: &[BatchResult]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+    for result in results {
+        let row = [
+            str_utils::csv_escape(&result.startpage),
+            str_utils::csv_escape(&result.endpage),
+            result.status.as_str().to_string(),
+            result.path_length.map_or(String::new(), |len| len.to_string()),
+            result.elapsed_ms.to_string(),
+            str_utils::csv_escape(&result.path.as_ref().map_or(String::new(), |path| path.join(CSV_PATH_SEPARATOR))),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+// Writes results as json lines, one result per line
+pub fn to_jsonl(results: &[BatchResult]) -> String {
+    results
+        .iter()
+        .map(|result| serde_json::to_string(result).unwrap() + "\n")
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(queries: &[BatchQuery]) -> Vec<(&str, &str)> {
+        queries
+            .iter()
+            .map(|query| (query.startpage.as_str(), query.endpage.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn csv_fields_handles_quotes() {
+        assert_eq!(csv_fields("Cat, Dog"), vec!["Cat", "Dog"]);
+        assert_eq!(csv_fields("\"Washington, D.C.\",Ulm"), vec!["Washington, D.C.", "Ulm"]);
+        assert_eq!(csv_fields("\"The \"\"Boss\"\"\",x"), vec!["The \"Boss\"", "x"]);
+        assert_eq!(csv_fields("a,,b"), vec!["a", "", "b"]);
+    }
+
+    #[test]
+    fn csv_fields_reads_what_csv_escape_writes() {
+        for title in ["Plain", "Comma, here", "\"Quoted\"", "Both, \"of them\""] {
+            let line = format!("{},end", str_utils::csv_escape(title));
+            assert_eq!(csv_fields(&line), vec![title, "end"]);
+        }
+    }
+
+    #[test]
+    fn parse_csv_skips_the_header_and_blank_lines() {
+        let queries = parse_csv("startpage,endpage\nCat,Dog\n\n\"Paris, Texas\",Texas\n").unwrap();
+        assert_eq!(pairs(&queries), vec![("Cat", "Dog"), ("Paris, Texas", "Texas")]);
+    }
+
+    #[test]
+    fn parse_csv_only_skips_a_header_on_the_first_line() {
+        let queries = parse_csv("Cat,Dog\nstartpage,endpage\n").unwrap();
+        assert_eq!(pairs(&queries), vec![("Cat", "Dog"), ("startpage", "endpage")]);
+    }
+
+    #[test]
+    fn parse_csv_needs_two_titles_per_line() {
+        assert_eq!(parse_csv("Cat,Dog\nCat\n").err().unwrap(), "Line 2 needs a start page and an end page");
+    }
+}
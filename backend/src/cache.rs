@@ -0,0 +1,122 @@
+use lru::LruCache;
+use serde::Serialize;
+use std::fs;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+// Number of results kept when WIKIMAPIA_PATH_CACHE_SIZE isn't set
+const DEFAULT_CAPACITY: usize = 1024;
+
+// Identifies a version of the database file on disk. If either value changes the file has been
+// reseeded or swapped out (e.g. a new main.db mounted into the container) and cached results are stale
+type DbStamp = (SystemTime, u64);
+
+#[derive(Serialize)]
+pub struct CacheStats {
+    pub capacity: usize,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+struct CacheInner<K: Hash + Eq, V> {
+    entries: Option<LruCache<K, V>>,
+    db_stamp: Option<DbStamp>,
+}
+
+/* Bounded least recently used cache for query results. A capacity of 0 disables caching entirely,
+every lookup is then counted as a miss. The cache remembers which version of the database its entries
+were computed against and drops everything as soon as that file changes */
+pub struct QueryCache<K: Hash + Eq, V: Clone> {
+    capacity: usize,
+    inner: Mutex<CacheInner<K, V>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<K: Hash + Eq, V: Clone> QueryCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        QueryCache {
+            capacity,
+            inner: Mutex::new(CacheInner {
+                entries: NonZeroUsize::new(capacity).map(LruCache::new),
+                db_stamp: None,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, key: &K, db_path: &str) -> Option<V> {
+        // The file is looked at before taking the lock, so lookups don't wait on each other's disk access
+        let stamp = db_stamp(db_path);
+        let mut inner = self.inner.lock().unwrap();
+        inner.invalidate_if_changed(stamp);
+        let value = inner.entries.as_mut().and_then(|entries| entries.get(key).cloned());
+        match value {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        value
+    }
+
+    pub fn insert(&self, key: K, value: V, db_path: &str) {
+        let stamp = db_stamp(db_path);
+        let mut inner = self.inner.lock().unwrap();
+        inner.invalidate_if_changed(stamp);
+        if let Some(entries) = inner.entries.as_mut() {
+            entries.put(key, value);
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(entries) = inner.entries.as_mut() {
+            entries.clear();
+        }
+        inner.db_stamp = None;
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.inner.lock().unwrap();
+        CacheStats {
+            capacity: self.capacity,
+            entries: inner.entries.as_ref().map_or(0, |entries| entries.len()),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> CacheInner<K, V> {
+    fn invalidate_if_changed(&mut self, stamp: Option<DbStamp>) {
+        if stamp != self.db_stamp {
+            if let Some(entries) = self.entries.as_mut() {
+                entries.clear();
+            }
+            self.db_stamp = stamp;
+        }
+    }
+}
+
+fn db_stamp(db_path: &str) -> Option<DbStamp> {
+    let metadata = fs::metadata(db_path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+// Reads the cache capacity from the WIKIMAPIA_PATH_CACHE_SIZE environment variable
+pub fn capacity_from_env() -> usize {
+    match std::env::var("WIKIMAPIA_PATH_CACHE_SIZE") {
+        Ok(val) => val.trim().parse().unwrap_or_else(|_| {
+            eprintln!(
+                "Invalid WIKIMAPIA_PATH_CACHE_SIZE {:?}, using default of {}",
+                val, DEFAULT_CAPACITY
+            );
+            DEFAULT_CAPACITY
+        }),
+        Err(_) => DEFAULT_CAPACITY,
+    }
+}
@@ -0,0 +1,450 @@
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use crate::search::has_table;
+use crate::str_utils;
+
+// Levels of subcategories listed when no depth is given, and the most that can be asked for
+pub const DEFAULT_TREE_DEPTH: usize = 2;
+pub const MAX_TREE_DEPTH: usize = 10;
+// Levels of subcategories a category filter on a search includes when no depth is given
+pub const DEFAULT_SUBTREE_DEPTH: usize = 3;
+// Most categories a tree lists, past that it's cut short
+const MAX_TREE_NODES: usize = 2000;
+// Number of articles of the category listed when no limit is given, and the most that can be asked for
+pub const DEFAULT_ARTICLE_LIMIT: usize = 100;
+pub const MAX_ARTICLE_LIMIT: usize = 1000;
+/* Most levels of parent categories climbed from each article when looking for a path through the hierarchy, and the
+most categories visited from each of them */
+const MAX_PATH_DEPTH: usize = 12;
+const MAX_PATH_CATEGORIES: usize = 200_000;
+
+// Why a category query has no answer
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum CategoryError {
+    // The database was seeded before categories were collected
+    NoCategories,
+    PageNotFound { title: String },
+    // The category has no page, no articles and no subcategories
+    CategoryNotFound { title: String },
+    // The articles share no category within MAX_PATH_DEPTH levels
+    NoPath,
+}
+
+impl std::fmt::Display for CategoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CategoryError::NoCategories => write!(f, "The database was seeded before categories were collected"),
+            CategoryError::PageNotFound { title } => write!(f, "Page {} doesn't exist", title),
+            CategoryError::CategoryNotFound { title } => write!(f, "Category {} doesn't exist", title),
+            CategoryError::NoPath => write!(
+                f,
+                "The articles don't share a category within {} levels of their own",
+                MAX_PATH_DEPTH
+            ),
+        }
+    }
+}
+
+// Result of a category query, either the answer or why there isn't one
+pub type CategoryResult<T> = Result<T, CategoryError>;
+
+/* Categories of an article, ordered by title. Hidden maintenance categories like "Articles with short description"
+are listed apart from the others */
+#[derive(Serialize)]
+pub struct PageCategories {
+    pub title: String,
+    pub page_id: usize,
+    pub categories: Vec<String>,
+    pub hidden: Vec<String>,
+}
+
+// A category and its subcategories, articles counts the articles directly in it
+#[derive(Serialize)]
+pub struct CategoryNode {
+    pub title: String,
+    pub articles: usize,
+    pub subcategories: Vec<CategoryNode>,
+}
+
+/* A category with its subcategories depth levels down. The category graph has cycles, every category is listed once
+at the shallowest level it's found. truncated is set if the tree was cut short after MAX_TREE_NODES categories.
+parents are the categories it's a subcategory of and articles the first limit of the articles directly in it */
+#[derive(Serialize)]
+pub struct CategoryTree {
+    pub title: String,
+    pub hidden: bool,
+    pub parents: Vec<String>,
+    pub articles: Vec<String>,
+    pub depth: usize,
+    pub categories: usize,
+    pub truncated: bool,
+    pub tree: CategoryNode,
+}
+
+/* Shortest path between two articles through the category hierarchy. It climbs from the start article through its
+categories and their parents up to common_category, the closest category both articles are under, then goes back
+down to the end article. up and down count the links on each side. Categories in path have the "Category:" prefix,
+hidden categories aren't used */
+#[derive(Serialize)]
+pub struct CategoryPath {
+    pub start_page: String,
+    pub end_page: String,
+    pub common_category: String,
+    pub up: usize,
+    pub down: usize,
+    pub path: Vec<String>,
+    pub elapsed_ms: u128,
+}
+
+// Read only view of the category tables, None if the database has none
+struct Categories {
+    conn: Connection,
+}
+
+impl Categories {
+    fn open(db_path: &str) -> rusqlite::Result<Option<Categories>> {
+        let conn = Connection::open(db_path)?;
+        if !has_table(&conn, "CATEGORY_MEMBERS")? {
+            return Ok(None);
+        }
+        Ok(Some(Categories { conn }))
+    }
+
+    // Id and title of the article a title refers to, following a redirect if the title isn't an article
+    fn resolve_page(&self, title: &str) -> rusqlite::Result<Option<(usize, String)>> {
+        self.conn
+            .prepare_cached(
+                "select id, page_title from PAGES where page_title = (?1)
+                union all
+                select p.id, p.page_title from REDIRECTS r join PAGES p on p.page_title = r.redirect_title
+                    where r.page_title = (?1)
+                limit 1",
+            )?
+            .query_row(params![title], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()
+    }
+
+    // Categories of an article with whether each one is hidden, ordered by title
+    fn page_categories(&self, page_id: usize) -> rusqlite::Result<Vec<(String, bool)>> {
+        let mut stmt = self.conn.prepare_cached(
+            "select m.category, coalesce(c.hidden, 0) from CATEGORY_MEMBERS m
+            left join CATEGORIES c on c.title = m.category
+            where m.page_id = (?1) order by m.category",
+        )?;
+        let categories = stmt.query_map(params![page_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        categories.collect()
+    }
+
+    // Categories a category is a subcategory of with whether each one is hidden, ordered by title
+    fn parents(&self, category: &str) -> rusqlite::Result<Vec<(String, bool)>> {
+        let mut stmt = self.conn.prepare_cached(
+            "select s.parent, coalesce(p.hidden, 0) from CATEGORIES c
+            join SUBCATEGORIES s on s.category_id = c.id
+            left join CATEGORIES p on p.title = s.parent
+            where c.title = (?1) order by s.parent",
+        )?;
+        let parents = stmt.query_map(params![category], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        parents.collect()
+    }
+
+    fn subcategories(&self, category: &str) -> rusqlite::Result<Vec<String>> {
+        let mut stmt = self.conn.prepare_cached(
+            "select c.title from SUBCATEGORIES s join CATEGORIES c on c.id = s.category_id
+            where s.parent = (?1) order by c.title",
+        )?;
+        let subcategories = stmt.query_map(params![category], |row| row.get(0))?;
+        subcategories.collect()
+    }
+
+    fn article_count(&self, category: &str) -> rusqlite::Result<usize> {
+        self.conn
+            .prepare_cached("select count(*) from CATEGORY_MEMBERS where category = (?1)")?
+            .query_row(params![category], |row| row.get(0))
+    }
+
+    fn articles(&self, category: &str, limit: usize) -> rusqlite::Result<Vec<String>> {
+        let mut stmt = self.conn.prepare_cached(
+            "select p.page_title from CATEGORY_MEMBERS m join PAGES p on p.id = m.page_id
+            where m.category = (?1) order by p.page_title limit (?2)",
+        )?;
+        let articles = stmt.query_map(params![category, limit as i64], |row| row.get(0))?;
+        articles.collect()
+    }
+
+    // Whether the category has a page, None if it doesn't
+    fn hidden(&self, category: &str) -> rusqlite::Result<Option<bool>> {
+        self.conn
+            .prepare_cached("select hidden from CATEGORIES where title = (?1)")?
+            .query_row(params![category], |row| row.get(0))
+            .optional()
+    }
+}
+
+// Categories of the article title refers to, title can be a redirect
+pub fn page_categories(db_path: &str, title: &str) -> rusqlite::Result<CategoryResult<PageCategories>> {
+    let Some(categories) = Categories::open(db_path)? else {
+        return Ok(Err(CategoryError::NoCategories));
+    };
+    let Some((page_id, title)) = categories.resolve_page(&str_utils::normalize_title(title))? else {
+        return Ok(Err(CategoryError::PageNotFound {
+            title: title.to_string(),
+        }));
+    };
+    let mut page = PageCategories {
+        title,
+        page_id,
+        categories: Vec::new(),
+        hidden: Vec::new(),
+    };
+    for (category, hidden) in categories.page_categories(page_id)? {
+        if hidden {
+            page.hidden.push(category);
+        } else {
+            page.categories.push(category);
+        }
+    }
+    Ok(Ok(page))
+}
+
+/* Name of a category as it's stored, without the "Category:" prefix. Both "Category:Felines" and "Felines" name
+the same category */
+pub fn category_title(title: &str) -> String {
+    str_utils::category_name(title).unwrap_or_else(|| str_utils::normalize_title(title))
+}
+
+/* Ids of the articles in a category or in its subcategories down to depth levels, depth 0 being the articles directly
+in it. None if the category has no page, no articles and no subcategories, which is every category when the database
+has no categories */
+pub fn subtree_pages(conn: &Connection, category: &str, depth: usize) -> rusqlite::Result<Option<HashSet<usize>>> {
+    if !has_table(conn, "CATEGORY_MEMBERS")? {
+        return Ok(None);
+    }
+    let exists: bool = conn
+        .prepare_cached(
+            "select exists(select 1 from CATEGORIES where title = (?1))
+              or exists(select 1 from CATEGORY_MEMBERS where category = (?1))
+              or exists(select 1 from SUBCATEGORIES where parent = (?1))",
+        )?
+        .query_row(params![category], |row| row.get(0))?;
+    if !exists {
+        return Ok(None);
+    }
+    // union drops repeated (title, level) rows, the level bound is what stops it going round cycles
+    let mut stmt = conn.prepare_cached(
+        "with recursive subtree(title, level) as (
+          select (?1), 0
+          union
+          select c.title, t.level + 1 from subtree t
+          join SUBCATEGORIES s on s.parent = t.title
+          join CATEGORIES c on c.id = s.category_id
+          where t.level < (?2)
+        )
+        select distinct m.page_id from subtree t join CATEGORY_MEMBERS m on m.category = t.title",
+    )?;
+    let pages = stmt.query_map(params![category, depth as i64], |row| row.get(0))?;
+    pages.collect::<rusqlite::Result<HashSet<usize>>>().map(Some)
+}
+
+/* Subcategories of a category depth levels down, searched breadth first so every category sits at the shallowest
+level it's found, and the first limit articles directly in it. title can have the "Category:" prefix or not */
+pub fn category_tree(
+    db_path: &str,
+    title: &str,
+    depth: usize,
+    limit: usize,
+) -> rusqlite::Result<CategoryResult<CategoryTree>> {
+    let Some(categories) = Categories::open(db_path)? else {
+        return Ok(Err(CategoryError::NoCategories));
+    };
+    let title = category_title(title);
+    let hidden = categories.hidden(&title)?;
+    let articles = categories.articles(&title, limit)?;
+    let mut children: HashMap<String, Vec<String>> = HashMap::new();
+    let mut seen: HashSet<String> = HashSet::from([title.clone()]);
+    let mut frontier = vec![title.clone()];
+    let mut truncated = false;
+    'levels: for _ in 0..depth {
+        let mut next = Vec::new();
+        for category in &frontier {
+            let mut kept = Vec::new();
+            for subcategory in categories.subcategories(category)? {
+                if seen.len() == MAX_TREE_NODES {
+                    children.insert(category.clone(), kept);
+                    truncated = true;
+                    break 'levels;
+                }
+                if seen.insert(subcategory.clone()) {
+                    kept.push(subcategory.clone());
+                    next.push(subcategory);
+                }
+            }
+            children.insert(category.clone(), kept);
+        }
+        frontier = next;
+    }
+    if hidden.is_none() && articles.is_empty() && children.get(&title).is_none_or(Vec::is_empty) {
+        return Ok(Err(CategoryError::CategoryNotFound { title }));
+    }
+
+    let tree = node(&categories, &title, &mut children)?;
+    Ok(Ok(CategoryTree {
+        parents: categories.parents(&title)?.into_iter().map(|(parent, _)| parent).collect(),
+        hidden: hidden.unwrap_or(false),
+        articles,
+        depth,
+        categories: seen.len(),
+        truncated,
+        tree,
+        title,
+    }))
+}
+
+// Builds the nested tree under a category from the subcategories found for every category
+fn node(
+    categories: &Categories,
+    title: &str,
+    children: &mut HashMap<String, Vec<String>>,
+) -> rusqlite::Result<CategoryNode> {
+    let subcategories = children
+        .remove(title)
+        .unwrap_or_default()
+        .iter()
+        .map(|subcategory| node(categories, subcategory, children))
+        .collect::<rusqlite::Result<Vec<CategoryNode>>>()?;
+    Ok(CategoryNode {
+        title: title.to_string(),
+        articles: categories.article_count(title)?,
+        subcategories,
+    })
+}
+
+/* Categories reachable by climbing from an article, each with its distance in links from the article and the
+category it was reached from, None for the article's own categories. Hidden categories are skipped */
+struct Ancestors {
+    found: HashMap<String, (usize, Option<String>)>,
+    frontier: Vec<String>,
+    distance: usize,
+}
+
+impl Ancestors {
+    fn new(categories: &Categories, page_id: usize) -> rusqlite::Result<Ancestors> {
+        let mut found = HashMap::new();
+        let mut frontier = Vec::new();
+        for (category, hidden) in categories.page_categories(page_id)? {
+            if !hidden {
+                found.insert(category.clone(), (1, None));
+                frontier.push(category);
+            }
+        }
+        Ok(Ancestors {
+            found,
+            frontier,
+            distance: 1,
+        })
+    }
+
+    // Climbs one more level, returns the categories found on it
+    fn climb(&mut self, categories: &Categories) -> rusqlite::Result<&[String]> {
+        let mut next = Vec::new();
+        for category in &self.frontier {
+            for (parent, hidden) in categories.parents(category)? {
+                if hidden || self.found.len() >= MAX_PATH_CATEGORIES || self.found.contains_key(&parent) {
+                    continue;
+                }
+                self.found.insert(parent.clone(), (self.distance + 1, Some(category.clone())));
+                next.push(parent);
+            }
+        }
+        self.distance += 1;
+        self.frontier = next;
+        Ok(&self.frontier)
+    }
+
+    // Categories from the article's own category up to category
+    fn chain(&self, category: &str) -> Vec<String> {
+        let mut chain = vec![category.to_string()];
+        while let Some((_, Some(child))) = self.found.get(chain.last().unwrap()) {
+            chain.push(child.clone());
+        }
+        chain.reverse();
+        chain
+    }
+}
+
+/* Shortest path between two articles that climbs the category hierarchy from the start article to a category both
+articles are under and goes back down to the end article. Every category above the start article is found first,
+then the end article's categories are climbed level by level until no closer common category can turn up. Titles
+can be redirects */
+pub fn category_path(
+    db_path: &str,
+    start_page: &str,
+    end_page: &str,
+) -> rusqlite::Result<CategoryResult<CategoryPath>> {
+    let search_start = Instant::now();
+    let Some(categories) = Categories::open(db_path)? else {
+        return Ok(Err(CategoryError::NoCategories));
+    };
+    let mut pages = Vec::new();
+    for title in [start_page, end_page] {
+        match categories.resolve_page(&str_utils::normalize_title(title))? {
+            Some(page) => pages.push(page),
+            None => {
+                return Ok(Err(CategoryError::PageNotFound {
+                    title: title.to_string(),
+                }));
+            }
+        }
+    }
+    let (end_id, end_title) = pages.pop().unwrap();
+    let (start_id, start_title) = pages.pop().unwrap();
+
+    let mut up = Ancestors::new(&categories, start_id)?;
+    while up.distance < MAX_PATH_DEPTH && !up.climb(&categories)?.is_empty() {}
+
+    // Closest common category as (links up, links down, category)
+    let mut best: Option<(usize, usize, String)> = None;
+    let mut down = Ancestors::new(&categories, end_id)?;
+    let mut level: Vec<String> = down.frontier.clone();
+    loop {
+        for category in &level {
+            let Some(&(distance, _)) = up.found.get(category) else {
+                continue;
+            };
+            if best.as_ref().is_none_or(|(u, d, _)| distance + down.distance < u + d) {
+                best = Some((distance, down.distance, category.clone()));
+            }
+        }
+        // Categories on the next level are at least one link further down and one link up
+        let done = best.as_ref().is_some_and(|(u, d, _)| u + d <= down.distance + 2);
+        if done || down.distance >= MAX_PATH_DEPTH {
+            break;
+        }
+        level = down.climb(&categories)?.to_vec();
+        if level.is_empty() {
+            break;
+        }
+    }
+
+    let Some((up_links, down_links, common)) = best else {
+        return Ok(Err(CategoryError::NoPath));
+    };
+    let mut path = vec![start_title.clone()];
+    path.extend(up.chain(&common).into_iter().map(|category| format!("Category:{}", category)));
+    let mut below = down.chain(&common);
+    below.pop();
+    path.extend(below.into_iter().rev().map(|category| format!("Category:{}", category)));
+    path.push(end_title.clone());
+    Ok(Ok(CategoryPath {
+        start_page: start_title,
+        end_page: end_title,
+        common_category: common,
+        up: up_links,
+        down: down_links,
+        path,
+        elapsed_ms: search_start.elapsed().as_millis(),
+    }))
+}
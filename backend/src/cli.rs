@@ -0,0 +1,948 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::process::ExitCode;
+use std::time::Instant;
+
+use crate::search::{
+    self, CategoryFilter, EdgeType, EdgeTypes, FoundPath, PathMode, SearchOptions, TieBreak, Unreachable,
+};
+use crate::categories::{self, CategoryNode};
+use crate::components;
+use crate::degrees::{self, DegreeStats};
+use crate::depth::{self, DepthReport, Direction};
+use crate::graph_stats::{self, GraphStatsOptions};
+use crate::pagerank::{self, PagerankOptions};
+use crate::{DB_PATH, DEFAULT_DB_PATH, PathResult, batch, db_path, db_stats, page, str_utils};
+
+/* Command line of the backend. Every command except shell runs once and exits, so it can be scripted. Results are
+printed as text meant for people, or as the same json the server responds with when --format json is given. Errors
+go to stderr and make the command exit with 1 */
+#[derive(Parser)]
+#[command(name = "wikimapia-backend", version, about = "Shortest paths between Wikipedia articles")]
+pub struct Cli {
+    #[arg(long, global = true, default_value = DEFAULT_DB_PATH, help = "Database to use")]
+    db: String,
+    #[arg(long, global = true, value_enum, default_value_t = Format::Table, help = "How results are printed")]
+    format: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Json,
+    Table,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Downloads the latest dump and seeds the database from scratch")]
+    Seed,
+    #[command(about = "Continues a seed that was interrupted, skipping the sections already seeded")]
+    Resume,
+    #[command(about = "Finds the shortest path between two articles")]
+    Path(PathArgs),
+    #[command(about = "Finds shortest paths for pairs read from a csv or jsonl file, searched in parallel")]
+    Batch {
+        #[arg(help = "startpage,endpage csv if the file name ends with .csv, json lines otherwise")]
+        input: String,
+        #[arg(long, help = "File to write csv to, or json lines with --format json, stdout if not given")]
+        output: Option<String>,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    #[command(about = "Number of articles at each distance from an article and the farthest ones")]
+    Depth {
+        title: String,
+        #[arg(long, help = "Follow links backwards, counting the articles that can reach the article instead")]
+        reverse: bool,
+        #[arg(long, value_parser = parse_edge_type, help = "Only follow links of this type, can be repeated")]
+        edge_type: Vec<EdgeType>,
+        #[arg(long, value_parser = parse_edge_type, help = "Don't follow links of this type, can be repeated")]
+        exclude_edge_type: Vec<EdgeType>,
+        #[arg(long, help = "Only go through articles in this category or its subcategories")]
+        category: Option<String>,
+        #[arg(
+            long,
+            requires = "category",
+            default_value_t = categories::DEFAULT_SUBTREE_DEPTH,
+            help = "Levels of subcategories of --category included"
+        )]
+        category_depth: usize,
+        #[arg(long, default_value_t = depth::DEFAULT_FARTHEST_LIMIT, help = "Number of farthest articles to show")]
+        limit: usize,
+    },
+    #[command(about = "Shows an article's description, degree, links and redirects")]
+    Page { title: String },
+    #[command(about = "Lists the articles linking to an article")]
+    Backlinks {
+        title: String,
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        #[arg(long, default_value_t = page::DEFAULT_BACKLINK_LIMIT)]
+        limit: usize,
+    },
+    #[command(about = "Searches article titles and descriptions, needs the full text search index")]
+    Find {
+        query: String,
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        #[arg(long, default_value_t = page::DEFAULT_TEXT_SEARCH_LIMIT)]
+        limit: usize,
+    },
+    #[command(about = "Lists the titles linked to by the most articles that aren't articles themselves")]
+    Wanted {
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        #[arg(long, default_value_t = page::DEFAULT_WANTED_LIMIT)]
+        limit: usize,
+    },
+    #[command(about = "Lists the links of an article that don't lead to an article")]
+    RedLinks { title: String },
+    #[command(about = "Lists the categories of an article")]
+    Categories { title: String },
+    #[command(about = "Shows the subcategories of a category and the articles in it")]
+    Category {
+        title: String,
+        #[arg(long, default_value_t = categories::DEFAULT_TREE_DEPTH, help = "Levels of subcategories to show")]
+        depth: usize,
+        #[arg(long, default_value_t = categories::DEFAULT_ARTICLE_LIMIT, help = "Number of articles to show")]
+        limit: usize,
+    },
+    #[command(about = "Finds the shortest path between two articles through the category hierarchy")]
+    CategoryPath { start: String, end: String },
+    #[command(about = "Shows the size of the database, its most linked to articles and the degree distribution")]
+    Stats {
+        #[arg(long, default_value_t = db_stats::DEFAULT_STATS_TOP, help = "Number of articles to show in every list")]
+        top: usize,
+        #[arg(long, value_enum, help = "Write degrees as csv for plotting instead")]
+        csv: Option<CsvTable>,
+        #[arg(long, requires = "csv", help = "File to write the csv to, stdout if not given")]
+        output: Option<String>,
+    },
+    #[command(about = "Counts the links into and out of every article, run it after seeding")]
+    Degrees {
+        #[arg(long, default_value_t = db_stats::DEFAULT_STATS_TOP, help = "Number of articles to show in every list")]
+        top: usize,
+    },
+    #[command(about = "Writes every link as source,target,edge_type csv, or json lines with --format json")]
+    Export {
+        #[arg(long, help = "File to write to, stdout if not given")]
+        output: Option<String>,
+    },
+    #[command(about = "Starts the http server", alias = "server")]
+    Serve {
+        #[arg(long, default_value = "0.0.0.0")]
+        host: String,
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+    },
+    #[command(about = "Checks that the database is complete and usable")]
+    Check,
+    #[command(about = "Finds the strongly connected components of the article graph, run it after seeding")]
+    Components {
+        #[arg(
+            long,
+            default_value_t = components::DEFAULT_ISOLATED_LIMIT,
+            help = "Number of components cut off from the giant component to list"
+        )]
+        limit: usize,
+    },
+    #[command(about = "Computes the PageRank of every article, run it after seeding")]
+    Pagerank {
+        #[arg(long, default_value_t = pagerank::DEFAULT_DAMPING, help = "Chance of following a link")]
+        damping: f64,
+        #[arg(long, default_value_t = pagerank::DEFAULT_TOLERANCE, help = "Stop once scores change less than this")]
+        tolerance: f64,
+        #[arg(long, default_value_t = pagerank::DEFAULT_MAX_ITERATIONS, help = "Most iterations to run")]
+        iterations: usize,
+    },
+    #[command(about = "Estimates the diameter and average path length of the article graph")]
+    GraphStats {
+        #[arg(long, default_value_t = graph_stats::DEFAULT_SAMPLES, help = "Articles to measure path lengths from")]
+        samples: usize,
+        #[arg(long, default_value_t = 0, help = "Seed picking the sampled articles")]
+        seed: u64,
+        #[arg(
+            long,
+            default_value_t = graph_stats::DEFAULT_MAX_SWEEPS,
+            help = "Most searches spent narrowing the bounds of the diameter"
+        )]
+        max_sweeps: usize,
+    },
+    #[command(about = "Starts the interactive shell", alias = "cli")]
+    Shell,
+}
+
+#[derive(Args)]
+struct PathArgs {
+    start: String,
+    end: String,
+    #[arg(long, conflicts_with = "k", help = "Count every shortest path and show --limit of them from --offset")]
+    all: bool,
+    #[arg(long, default_value_t = 0)]
+    offset: usize,
+    #[arg(long, default_value_t = search::DEFAULT_PATH_LIMIT)]
+    limit: usize,
+    #[arg(long, help = "Find the k shortest loopless paths instead")]
+    k: Option<usize>,
+    #[command(flatten)]
+    search: SearchArgs,
+}
+
+// Options of a path search, shared by path and batch
+#[derive(Args)]
+struct SearchArgs {
+    #[arg(long, help = "Article that can't be used as an intermediate page, /pattern/ for a regex, can be repeated")]
+    avoid: Vec<String>,
+    #[arg(long, help = "Don't use disambiguation pages as intermediate pages")]
+    avoid_disambiguation: bool,
+    #[arg(long, help = "Article the path has to go through, can be repeated")]
+    via: Vec<String>,
+    #[arg(long, help = "Most links the path can follow")]
+    max_depth: Option<usize>,
+    #[arg(long, help = "Only go through articles in this category or its subcategories")]
+    category: Option<String>,
+    #[arg(
+        long,
+        requires = "category",
+        default_value_t = categories::DEFAULT_SUBTREE_DEPTH,
+        help = "Levels of subcategories of --category included"
+    )]
+    category_depth: usize,
+    #[arg(long, value_parser = parse_edge_type, help = "Only follow links of this type, can be repeated")]
+    edge_type: Vec<EdgeType>,
+    #[arg(long, value_parser = parse_edge_type, help = "Don't follow links of this type, can be repeated")]
+    exclude_edge_type: Vec<EdgeType>,
+    #[arg(
+        long,
+        default_value = "none",
+        help = "none, lexicographic, most_linked, least_linked, pagerank or random"
+    )]
+    tie_break: String,
+    #[arg(long, default_value_t = 0, help = "Seed for --tie-break random")]
+    seed: u64,
+}
+
+fn parse_edge_type(name: &str) -> Result<EdgeType, String> {
+    EdgeType::parse(name).ok_or_else(|| format!("unknown edge type {}, expected wikilink or etymology", name))
+}
+
+// Category filter given by --category and --category-depth, None without --category
+fn category_filter(category: Option<&str>, depth: usize) -> Result<Option<CategoryFilter>, String> {
+    category.map(|category| CategoryFilter::new(category, depth)).transpose()
+}
+
+// Edge types left after taking the excluded ones out of the included ones, every type if none were included
+fn edge_types(included: &[EdgeType], excluded: &[EdgeType]) -> EdgeTypes {
+    let mut edge_types = if included.is_empty() {
+        EdgeTypes::ALL
+    } else {
+        EdgeTypes::NONE
+    };
+    for &edge_type in included {
+        edge_types.insert(edge_type);
+    }
+    for &edge_type in excluded {
+        edge_types.remove(edge_type);
+    }
+    edge_types
+}
+
+pub fn run(cli: Cli) -> ExitCode {
+    let _ = DB_PATH.set(cli.db.clone());
+    let result = match cli.command {
+        Command::Seed => crate::seed_db(false).map_err(|e| format!("Error seeding database: {}", e)),
+        Command::Resume => crate::seed_db(true).map_err(|e| format!("Error resuming seed: {}", e)),
+        Command::Path(args) => path(&args, cli.format),
+        Command::Batch { input, output, search } => {
+            search.options().and_then(|options| batch(&input, output.as_deref(), &options, cli.format))
+        }
+        Command::Depth {
+            title,
+            reverse,
+            edge_type,
+            exclude_edge_type,
+            category,
+            category_depth,
+            limit,
+        } => {
+            let direction = if reverse {
+                Direction::Reverse
+            } else {
+                Direction::Forward
+            };
+            let edge_types = edge_types(&edge_type, &exclude_edge_type);
+            category_filter(category.as_deref(), category_depth)
+                .and_then(|category| depth(&title, direction, edge_types, category.as_ref(), limit, cli.format))
+        }
+        Command::Page { title } => page_info(&title, cli.format),
+        Command::Backlinks { title, offset, limit } => backlinks(&title, offset, limit, cli.format),
+        Command::Find { query, offset, limit } => find(&query, offset, limit, cli.format),
+        Command::Wanted { offset, limit } => wanted(offset, limit, cli.format),
+        Command::RedLinks { title } => red_links(&title, cli.format),
+        Command::Categories { title } => page_categories(&title, cli.format),
+        Command::Category { title, depth, limit } => category_tree(&title, depth, limit, cli.format),
+        Command::CategoryPath { start, end } => category_path(&start, &end, cli.format),
+        Command::Stats { top, csv: None, .. } => stats(top, cli.format),
+        Command::Stats {
+            csv: Some(table),
+            output,
+            ..
+        } => stats_csv(table, output.as_deref()),
+        Command::Degrees { top } => compute_degrees(top, cli.format),
+        Command::Export { output } => export(output.as_deref(), cli.format),
+        Command::Serve { host, port } => actix_rt::System::new()
+            .block_on(crate::start_server(&host, port))
+            .map_err(|e| format!("Server failed: {}", e)),
+        Command::Check => check(cli.format),
+        Command::Components { limit } => components(limit, cli.format),
+        Command::Pagerank {
+            damping,
+            tolerance,
+            iterations,
+        } => pagerank(
+            &PagerankOptions {
+                damping,
+                tolerance,
+                max_iterations: iterations,
+            },
+            cli.format,
+        ),
+        Command::GraphStats {
+            samples,
+            seed,
+            max_sweeps,
+        } => graph_stats(
+            &GraphStatsOptions {
+                samples,
+                seed,
+                max_sweeps,
+            },
+            cli.format,
+        ),
+        Command::Shell => crate::shell::run().map_err(|e| format!("Shell failed: {}", e)),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap());
+}
+
+// A path on one line, links that aren't plain wikilinks are labelled with their edge type
+pub fn path_line(path: &FoundPath) -> String {
+    let mut line = path.pages[0].clone();
+    for (page, edge_type) in path.pages[1..].iter().zip(&path.edge_types) {
+        match edge_type {
+            EdgeType::Wikilink => line.push_str(" -> "),
+            _ => line.push_str(&format!(" -[{}]-> ", edge_type.as_str())),
+        }
+        line.push_str(page);
+    }
+    line
+}
+
+impl SearchArgs {
+    fn options(&self) -> Result<SearchOptions, String> {
+        let tie_break = TieBreak::parse(&self.tie_break, self.seed).ok_or_else(|| {
+            String::from(
+                "--tie-break must be one of none, lexicographic, most_linked, least_linked, pagerank or random",
+            )
+        })?;
+        let options = SearchOptions {
+            avoid: self.avoid.clone(),
+            avoid_disambiguation: self.avoid_disambiguation,
+            via: self.via.clone(),
+            category: category_filter(self.category.as_deref(), self.category_depth)?,
+            max_depth: self.max_depth,
+            edge_types: edge_types(&self.edge_type, &self.exclude_edge_type),
+            tie_break,
+        };
+        options.validate()?;
+        Ok(options)
+    }
+}
+
+fn path(args: &PathArgs, format: Format) -> Result<(), String> {
+    let options = args.search.options()?;
+    let mode = match args.k {
+        Some(k) if k == 0 || k > search::MAX_K => {
+            return Err(format!("--k must be between 1 and {}", search::MAX_K));
+        }
+        Some(_) if !options.via.is_empty() => {
+            return Err(String::from("--via isn't supported with --k"));
+        }
+        Some(k) => PathMode::KShortest { k },
+        None if args.all => PathMode::All {
+            offset: args.offset,
+            limit: args.limit.min(search::MAX_PATH_LIMIT),
+        },
+        None => PathMode::Single,
+    };
+    let result = crate::cached_shortest_path(&args.start, &args.end, mode, options, &mut |_| true)
+        .map_err(|e| format!("Error finding shortest path: {}", e))?;
+
+    let response = crate::path_response(&args.start, &args.end, &result);
+    if format == Format::Json {
+        return match response {
+            Ok(response) => {
+                print_json(&response);
+                Ok(())
+            }
+            Err(reason) => {
+                print_json(&serde_json::json!({
+                    "start_page": args.start,
+                    "end_page": args.end,
+                    "error": reason.error(),
+                    "reason": reason,
+                }));
+                Err(no_path_message(&reason))
+            }
+        };
+    }
+    match result {
+        PathResult::Single(Ok(path)) => {
+            println!("{}", path_line(&path));
+            println!("{} pages", path.pages.len());
+        }
+        PathResult::All(Ok(all_paths)) => {
+            for (i, path) in all_paths.paths.iter().enumerate() {
+                println!("{}. {}", all_paths.offset + i + 1, path_line(path));
+            }
+            println!("{} shortest paths", all_paths.total_paths);
+        }
+        PathResult::KShortest(Ok(paths)) => {
+            for (i, path) in paths.iter().enumerate() {
+                println!("{}. {} ({} pages)", i + 1, path_line(path), path.pages.len());
+            }
+        }
+        PathResult::Single(Err(reason))
+        | PathResult::All(Err(reason))
+        | PathResult::KShortest(Err(reason)) => return Err(no_path_message(&reason)),
+    }
+    Ok(())
+}
+
+/* Searches every pair of a csv or json lines file. Results are written as csv, or json lines with --format json, the
+same as POST /paths responds with */
+fn batch(input: &str, output: Option<&str>, options: &SearchOptions, format: Format) -> Result<(), String> {
+    let contents = std::fs::read_to_string(input).map_err(|e| format!("Couldn't read {}: {}", input, e))?;
+    let queries = if input.ends_with(".csv") {
+        batch::parse_csv(&contents)
+    } else {
+        batch::parse_jsonl(&contents)
+    }
+    .map_err(|e| format!("Couldn't parse {}: {}", input, e))?;
+
+    let batch_start = Instant::now();
+    let results =
+        batch::run_batch(db_path(), queries, options).map_err(|e| format!("Error running batch: {}", e))?;
+    let written = match format {
+        Format::Json => batch::to_jsonl(&results),
+        Format::Table => batch::to_csv(&results),
+    };
+    match output {
+        Some(output) => std::fs::write(output, written).map_err(|e| format!("Couldn't write {}: {}", output, e))?,
+        None => print!("{}", written),
+    }
+    let found = results
+        .iter()
+        .filter(|result| matches!(result.status, batch::BatchStatus::Ok))
+        .count();
+    eprintln!("Found paths for {}/{} pairs in {:?}", found, results.len(), batch_start.elapsed());
+    Ok(())
+}
+
+// Error printed when a search gives no path, a title or category that doesn't exist is reported on its own
+fn no_path_message(reason: &Unreachable) -> String {
+    match reason {
+        Unreachable::PageNotFound { .. } | Unreachable::CategoryNotFound { .. } => {
+            str_utils::capitalize_first_char(&reason.to_string())
+        }
+        _ => format!("No path found, {}", reason),
+    }
+}
+
+fn depth(
+    title: &str,
+    direction: Direction,
+    edge_types: EdgeTypes,
+    category: Option<&CategoryFilter>,
+    limit: usize,
+    format: Format,
+) -> Result<(), String> {
+    let report = depth::depth_report(db_path(), title, direction, edge_types, category, limit)
+        .map_err(|e| format!("Error finding depth: {}", e))?
+        .map_err(|reason| str_utils::capitalize_first_char(&reason.to_string()))?;
+    match format {
+        Format::Json => print_json(&report),
+        Format::Table => print_depth_report(&report),
+    }
+    Ok(())
+}
+
+// Distance distribution as a table followed by the farthest pages and a path to each of them
+pub fn print_depth_report(report: &DepthReport) {
+    match report.direction {
+        Direction::Forward => println!("{} reaches {} pages", report.title, report.reachable),
+        Direction::Reverse => println!("{} can be reached from {} pages", report.title, report.reachable),
+    }
+    println!("{:>8}  {:>10}", "Distance", "Pages");
+    for (distance, count) in report.distances.iter().enumerate() {
+        println!("{:>8}  {:>10}", distance, count);
+    }
+    println!(
+        "Farthest pages are {} links away, {} of them:",
+        report.eccentricity, report.farthest_total
+    );
+    for farthest in &report.farthest {
+        println!("  {}", path_line(&farthest.path));
+    }
+    if report.farthest_total > report.farthest.len() {
+        println!("  ... and {} more", report.farthest_total - report.farthest.len());
+    }
+    println!("Depth found in {}ms", report.elapsed_ms);
+}
+
+fn page_info(title: &str, format: Format) -> Result<(), String> {
+    let info = page::page_info(db_path(), title)
+        .map_err(|e| format!("Error finding page: {}", e))?
+        .ok_or_else(|| format!("Page {} doesn't exist", title))?;
+    match format {
+        Format::Json => print_json(&info),
+        Format::Table => {
+            let disambiguation = if info.disambiguation { ", disambiguation page" } else { "" };
+            println!("{} (id {}{})", info.title, info.page_id, disambiguation);
+            if let Some(description) = &info.description {
+                println!("{}", description);
+            }
+            println!("In degree: {}", info.in_degree);
+            println!("Out degree: {}", info.out_degree);
+            if let Some(pagerank) = info.pagerank {
+                println!("PageRank: {:.3e}", pagerank);
+            }
+            if !info.redirects.is_empty() {
+                println!("Redirects: {}", info.redirects.join(", "));
+            }
+            println!("Links:");
+            for link in &info.links {
+                let mut line = format!("  {}", link.title);
+                if let Some(redirect) = &link.redirect {
+                    line.push_str(&format!(" (via {})", redirect));
+                }
+                if link.edge_type != EdgeType::Wikilink {
+                    line.push_str(&format!(" [{}]", link.edge_type.as_str()));
+                }
+                println!("{}", line);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn backlinks(title: &str, offset: usize, limit: usize, format: Format) -> Result<(), String> {
+    let limit = limit.min(page::MAX_BACKLINK_LIMIT);
+    let backlinks = page::backlinks(db_path(), title, offset, limit)
+        .map_err(|e| format!("Error finding backlinks: {}", e))?
+        .ok_or_else(|| format!("Page {} doesn't exist", title))?;
+    match format {
+        Format::Json => print_json(&backlinks),
+        Format::Table => {
+            for backlink in &backlinks.backlinks {
+                match &backlink.redirect {
+                    Some(redirect) => println!("{} (via {})", backlink.title, redirect),
+                    None => println!("{}", backlink.title),
+                }
+            }
+            println!(
+                "{} pages link to {}: {} directly, {} only through redirects",
+                backlinks.total, backlinks.title, backlinks.direct, backlinks.via_redirect
+            );
+        }
+    }
+    Ok(())
+}
+
+fn find(query: &str, offset: usize, limit: usize, format: Format) -> Result<(), String> {
+    let limit = limit.min(page::MAX_TEXT_SEARCH_LIMIT);
+    let results = page::find(db_path(), query, offset, limit)
+        .map_err(|e| format!("Error searching: {}", e))?
+        .ok_or_else(|| String::from(page::NO_SEARCH_INDEX))?;
+    match format {
+        Format::Json => print_json(&serde_json::json!({
+            "query": query,
+            "offset": offset,
+            "limit": limit,
+            "results": results,
+        })),
+        Format::Table => {
+            for (i, result) in results.iter().enumerate() {
+                println!("{}. {}: {}", offset + i + 1, result.title, result.snippet);
+            }
+            println!("{} results", results.len());
+        }
+    }
+    Ok(())
+}
+
+fn wanted(offset: usize, limit: usize, format: Format) -> Result<(), String> {
+    let limit = limit.min(page::MAX_WANTED_LIMIT);
+    let wanted = page::most_wanted(db_path(), offset, limit)
+        .map_err(|e| format!("Error finding red links: {}", e))?
+        .ok_or_else(|| String::from(page::NO_RED_LINKS))?;
+    match format {
+        Format::Json => print_json(&wanted),
+        Format::Table => {
+            for (i, red_link) in wanted.wanted.iter().enumerate() {
+                println!("{}. {} ({} pages)", offset + i + 1, red_link.title, red_link.pages);
+            }
+            println!("{} titles are linked to without being articles", wanted.total);
+        }
+    }
+    Ok(())
+}
+
+fn red_links(title: &str, format: Format) -> Result<(), String> {
+    let page = page::red_links(db_path(), title)
+        .map_err(|e| format!("Error finding red links: {}", e))?
+        .ok_or_else(|| format!("Page {} doesn't exist", title))?;
+    let red_links = page.red_links.as_ref().ok_or_else(|| String::from(page::NO_RED_LINKS))?;
+    match format {
+        Format::Json => print_json(&page),
+        Format::Table => {
+            for red_link in red_links {
+                println!("{} ({} pages)", red_link.title, red_link.pages);
+            }
+            println!("{} links of {} don't lead to an article", red_links.len(), page.title);
+        }
+    }
+    Ok(())
+}
+
+fn page_categories(title: &str, format: Format) -> Result<(), String> {
+    let page = categories::page_categories(db_path(), title)
+        .map_err(|e| format!("Error finding categories: {}", e))?
+        .map_err(|e| e.to_string())?;
+    match format {
+        Format::Json => print_json(&page),
+        Format::Table => {
+            for category in &page.categories {
+                println!("{}", category);
+            }
+            println!("{} is in {} categories", page.title, page.categories.len());
+            if !page.hidden.is_empty() {
+                println!("Hidden: {}", page.hidden.join(", "));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn category_tree(title: &str, depth: usize, limit: usize, format: Format) -> Result<(), String> {
+    let depth = depth.min(categories::MAX_TREE_DEPTH);
+    let limit = limit.min(categories::MAX_ARTICLE_LIMIT);
+    let tree = categories::category_tree(db_path(), title, depth, limit)
+        .map_err(|e| format!("Error walking the category tree: {}", e))?
+        .map_err(|e| e.to_string())?;
+    match format {
+        Format::Json => print_json(&tree),
+        Format::Table => {
+            if !tree.parents.is_empty() {
+                println!("Parents: {}", tree.parents.join(", "));
+            }
+            print_category_node(&tree.tree, 0);
+            if tree.truncated {
+                println!("Cut short after {} categories", tree.categories);
+            }
+            for article in &tree.articles {
+                println!("  {}", article);
+            }
+            println!(
+                "{} of the {} articles in {}",
+                tree.articles.len(),
+                tree.tree.articles,
+                tree.title
+            );
+        }
+    }
+    Ok(())
+}
+
+// One category per line, indented by how far below the top of the tree it is
+fn print_category_node(node: &CategoryNode, level: usize) {
+    println!("{}{} ({} articles)", "  ".repeat(level), node.title, node.articles);
+    for subcategory in &node.subcategories {
+        print_category_node(subcategory, level + 1);
+    }
+}
+
+fn category_path(start: &str, end: &str, format: Format) -> Result<(), String> {
+    let path = categories::category_path(db_path(), start, end)
+        .map_err(|e| format!("Error finding a path through categories: {}", e))?
+        .map_err(|e| e.to_string())?;
+    match format {
+        Format::Json => print_json(&path),
+        Format::Table => {
+            println!("{}", path.path.join(" -> "));
+            println!(
+                "{} links up to {} and {} back down, found in {}ms",
+                path.up, path.common_category, path.down, path.elapsed_ms
+            );
+        }
+    }
+    Ok(())
+}
+
+fn stats(top: usize, format: Format) -> Result<(), String> {
+    let stats = db_stats::stats(db_path(), top).map_err(|e| format!("Error reading stats: {}", e))?;
+    match format {
+        Format::Json => print_json(&stats),
+        Format::Table => {
+            println!("Pages: {}", stats.pages);
+            println!("Links: {}", stats.links);
+            println!("Redirects: {}", stats.redirects);
+            println!("Most linked:");
+            for (i, page) in stats.most_linked.iter().enumerate() {
+                println!("  {}. {} ({})", i + 1, page.title, page.in_degree);
+            }
+            match &stats.degrees {
+                Some(degrees) => print_degree_stats(degrees, false),
+                None => println!("Run the degrees command for the degree distribution"),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn compute_degrees(top: usize, format: Format) -> Result<(), String> {
+    let stats = degrees::compute_degrees(db_path(), top).map_err(|e| format!("Error counting degrees: {}", e))?;
+    match format {
+        Format::Json => print_json(&stats),
+        Format::Table => print_degree_stats(&stats, true),
+    }
+    Ok(())
+}
+
+/* Degree distribution as text. stats already lists the most linked articles, counted the way SUGGESTIONS counts
+them, so they're only listed again when asked for */
+fn print_degree_stats(stats: &DegreeStats, most_linked: bool) {
+    let percent = |pages: usize| 100.0 * pages as f64 / stats.pages.max(1) as f64;
+    println!(
+        "Degrees: {} articles, {} links between them, {:.2} links per article",
+        stats.pages, stats.links, stats.mean_degree
+    );
+    println!("Highest in degree: {}, highest out degree: {}", stats.max_in_degree, stats.max_out_degree);
+    println!("Dead ends, without links to articles: {} ({:.1}%)", stats.dead_ends, percent(stats.dead_ends));
+    println!("Orphans, without links from articles: {} ({:.1}%)", stats.orphans, percent(stats.orphans));
+    for (name, histogram) in [("In degree", &stats.in_histogram), ("Out degree", &stats.out_histogram)] {
+        println!("{}:", name);
+        for bin in histogram {
+            let range = if bin.low == bin.high {
+                bin.low.to_string()
+            } else {
+                format!("{}-{}", bin.low, bin.high)
+            };
+            println!("  {:>9}: {} ({:.1}%)", range, bin.pages, percent(bin.pages));
+        }
+    }
+    let mut lists = vec![
+        ("Hubs, most links out", &stats.hubs),
+        ("Most linked dead ends", &stats.top_dead_ends),
+        ("Orphans with the most links out", &stats.top_orphans),
+    ];
+    if most_linked {
+        lists.insert(0, ("Most linked", &stats.most_linked));
+    }
+    for (name, pages) in lists {
+        if pages.is_empty() {
+            continue;
+        }
+        println!("{}:", name);
+        for (i, page) in pages.iter().enumerate() {
+            println!("  {}. {} (in {}, out {})", i + 1, page.title, page.in_degree, page.out_degree);
+        }
+    }
+}
+
+// What stats --csv writes, the histograms for plotting the distribution or the degrees of every article
+#[derive(Clone, Copy, ValueEnum)]
+enum CsvTable {
+    Histogram,
+    Pages,
+}
+
+fn stats_csv(table: CsvTable, output: Option<&str>) -> Result<(), String> {
+    let mut out: Box<dyn std::io::Write> = match output {
+        Some(output) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(output).map_err(|e| format!("Couldn't create {}: {}", output, e))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let not_computed = || String::from("Degrees haven't been computed, run the degrees command first");
+    match table {
+        CsvTable::Histogram => {
+            let degrees = rusqlite::Connection::open(db_path())
+                .and_then(|conn| degrees::degree_stats(&conn, 0))
+                .map_err(|e| format!("Error reading degrees: {}", e))?
+                .ok_or_else(not_computed)?;
+            degrees::write_histogram_csv(&degrees, &mut out).map_err(|e| format!("Error writing csv: {}", e))?;
+        }
+        CsvTable::Pages => {
+            let written = degrees::write_degrees_csv(db_path(), &mut out)
+                .map_err(|e| format!("Error writing csv: {}", e))?
+                .ok_or_else(not_computed)?;
+            eprintln!("Wrote the degrees of {} articles", written);
+        }
+    }
+    out.flush().map_err(|e| format!("Error writing csv: {}", e))
+}
+
+fn export(output: Option<&str>, format: Format) -> Result<(), String> {
+    let json = format == Format::Json;
+    let written = match output {
+        Some(output) => {
+            let file = std::fs::File::create(output).map_err(|e| format!("Couldn't create {}: {}", output, e))?;
+            db_stats::export_links(db_path(), json, &mut std::io::BufWriter::new(file))
+        }
+        None => db_stats::export_links(db_path(), json, &mut std::io::stdout().lock()),
+    }
+    .map_err(|e| format!("Error exporting links: {}", e))?;
+    eprintln!("Exported {} links", written);
+    Ok(())
+}
+
+fn components(limit: usize, format: Format) -> Result<(), String> {
+    let summary = components::compute_components(db_path(), limit)
+        .map_err(|e| format!("Error finding components: {}", e))?;
+    match format {
+        Format::Json => print_json(&summary),
+        Format::Table => {
+            let percent = |pages: usize| 100.0 * pages as f64 / summary.pages.max(1) as f64;
+            println!("Pages: {}, strongly connected components: {}", summary.pages, summary.components);
+            println!(
+                "Giant component: {} pages ({:.1}%)",
+                summary.giant_size,
+                percent(summary.giant_size)
+            );
+            println!(
+                "Can reach it: {} pages ({:.1}%)",
+                summary.in_component,
+                percent(summary.in_component)
+            );
+            println!(
+                "Reachable from it: {} pages ({:.1}%)",
+                summary.out_component,
+                percent(summary.out_component)
+            );
+            println!("Neither: {} pages ({:.1}%)", summary.isolated, percent(summary.isolated));
+            if !summary.isolated_components.is_empty() {
+                println!("Largest components cut off from it:");
+                for component in &summary.isolated_components {
+                    let more = if component.size > component.titles.len() { ", ..." } else { "" };
+                    println!("  {} pages: {}{}", component.size, component.titles.join(", "), more);
+                }
+            }
+            println!("Computed in {}ms", summary.elapsed_ms);
+        }
+    }
+    Ok(())
+}
+
+fn pagerank(options: &PagerankOptions, format: Format) -> Result<(), String> {
+    options.validate()?;
+    let summary =
+        pagerank::compute_pagerank(db_path(), options).map_err(|e| format!("Error computing PageRank: {}", e))?;
+    match format {
+        Format::Json => print_json(&summary),
+        Format::Table => {
+            println!(
+                "{} after {} iterations, change in the last one {:.3e}",
+                if summary.converged { "Converged" } else { "Didn't converge" },
+                summary.iterations,
+                summary.delta
+            );
+            println!(
+                "Pages: {}, links: {}, dangling pages: {}",
+                summary.pages, summary.links, summary.dangling
+            );
+            println!("Highest PageRank:");
+            for (i, page) in summary.top.iter().enumerate() {
+                println!("  {}. {} ({:.3e})", i + 1, page.title, page.pagerank);
+            }
+            println!("Computed in {}ms", summary.elapsed_ms);
+        }
+    }
+    Ok(())
+}
+
+fn graph_stats(options: &GraphStatsOptions, format: Format) -> Result<(), String> {
+    options.validate()?;
+    let stats = graph_stats::compute_graph_stats(db_path(), options)
+        .map_err(|e| format!("Error computing graph stats: {}", e))?
+        .ok_or_else(|| String::from("The database has no articles"))?;
+    match format {
+        Format::Json => print_json(&stats),
+        Format::Table => {
+            if let Some(seeded_at) = &stats.seeded_at {
+                println!("Database seeded at {}", seeded_at);
+            }
+            println!(
+                "Pages: {}, links: {}, giant component: {} pages",
+                stats.pages, stats.links, stats.giant_size
+            );
+            if stats.diameter_lower == stats.diameter_upper {
+                println!("Diameter: {} ({} searches)", stats.diameter_lower, stats.sweeps);
+            } else {
+                println!(
+                    "Diameter: between {} and {} ({} searches)",
+                    stats.diameter_lower, stats.diameter_upper, stats.sweeps
+                );
+            }
+            println!(
+                "Average path length: {:.3}, 95% confidence interval {:.3} to {:.3} ({} articles sampled)",
+                stats.avg_path_length, stats.avg_path_ci_low, stats.avg_path_ci_high, stats.samples
+            );
+            let pairs: u64 = stats.distances.iter().sum();
+            let mut within = 0;
+            for (d, &count) in stats.distances.iter().enumerate().skip(1) {
+                within += count;
+                println!(
+                    "  {} links: {} pairs ({:.1}% within)",
+                    d,
+                    count,
+                    100.0 * within as f64 / pairs.max(1) as f64
+                );
+            }
+            println!("Computed in {}ms", stats.elapsed_ms);
+        }
+    }
+    Ok(())
+}
+
+fn check(format: Format) -> Result<(), String> {
+    let checks = db_stats::check(db_path()).map_err(|e| format!("Error checking database: {}", e))?;
+    match format {
+        Format::Json => print_json(&checks),
+        Format::Table => {
+            for check in &checks {
+                let status = match check.status {
+                    db_stats::CheckStatus::Ok => "ok",
+                    db_stats::CheckStatus::Warning => "warning",
+                    db_stats::CheckStatus::Error => "error",
+                };
+                println!("[{}] {}: {}", status, check.name, check.detail);
+            }
+        }
+    }
+    if checks.iter().any(|check| check.status == db_stats::CheckStatus::Error) {
+        return Err(String::from("The database has errors"));
+    }
+    Ok(())
+}
@@ -0,0 +1,300 @@
+use rusqlite::{Connection, params};
+use serde::Serialize;
+use std::time::Instant;
+
+use crate::db_stats;
+use crate::pagerank::{self, LinkGraph};
+
+// Number of components not connected to the giant component that are listed, and titles shown for each of them
+pub const DEFAULT_ISOLATED_LIMIT: usize = 20;
+const ISOLATED_SAMPLE_TITLES: usize = 5;
+
+// A strongly connected component that can't reach the giant component and can't be reached from it
+#[derive(Serialize)]
+pub struct IsolatedComponent {
+    pub component: usize,
+    pub size: usize,
+    pub titles: Vec<String>,
+}
+
+/* Bow tie structure of the link graph. The giant component is the largest strongly connected component, every page in
+it can reach every other. in_component counts the pages outside it that can reach it, out_component the ones it can
+reach. isolated counts the pages in neither, isolated_components lists the largest components they form */
+#[derive(Serialize)]
+pub struct ComponentsSummary {
+    pub pages: usize,
+    pub components: usize,
+    pub giant_component: usize,
+    pub giant_size: usize,
+    pub in_component: usize,
+    pub out_component: usize,
+    pub isolated: usize,
+    pub isolated_components: Vec<IsolatedComponent>,
+    pub elapsed_ms: u128,
+}
+
+/* Strongly connected components found by Tarjan's algorithm, numbered in the order they're completed. That's a
+reverse topological order of the components, a link between two components always goes from a higher number to a
+lower one. members lists the pages of every component, those of component c are members[starts[c]..starts[c + 1]] */
+pub struct Components {
+    pub component: Vec<u32>,
+    members: Vec<u32>,
+    starts: Vec<usize>,
+}
+
+impl Components {
+    pub fn count(&self) -> usize {
+        self.starts.len() - 1
+    }
+
+    pub fn size(&self, c: usize) -> usize {
+        self.starts[c + 1] - self.starts[c]
+    }
+
+    pub fn members(&self, c: usize) -> &[u32] {
+        &self.members[self.starts[c]..self.starts[c + 1]]
+    }
+
+    // The largest component, None if the graph has no pages
+    pub fn giant(&self) -> Option<usize> {
+        (0..self.count()).max_by_key(|&c| self.size(c))
+    }
+}
+
+/* Tarjan's algorithm with an explicit stack of the pages being visited and how far through their links it is, so
+long chains of links don't overflow the call stack */
+pub fn tarjan(graph: &LinkGraph) -> Components {
+    const UNVISITED: u32 = u32::MAX;
+    let n = graph.ids.len();
+    let mut index = vec![UNVISITED; n];
+    let mut lowlink = vec![0u32; n];
+    let mut on_stack = vec![false; n];
+    let mut stack: Vec<u32> = Vec::new();
+    let mut visiting: Vec<(u32, usize)> = Vec::new();
+    let mut next_index = 0u32;
+    let mut components = Components {
+        component: vec![UNVISITED; n],
+        members: Vec::with_capacity(n),
+        starts: vec![0],
+    };
+
+    for root in 0..n as u32 {
+        if index[root as usize] != UNVISITED {
+            continue;
+        }
+        visiting.push((root, 0));
+        while let Some(&mut (page, ref mut next_link)) = visiting.last_mut() {
+            let v = page as usize;
+            if *next_link == 0 && index[v] == UNVISITED {
+                index[v] = next_index;
+                lowlink[v] = next_index;
+                next_index += 1;
+                stack.push(page);
+                on_stack[v] = true;
+            }
+            let links = graph.links(v);
+            if let Some(&target) = links.get(*next_link) {
+                *next_link += 1;
+                let w = target as usize;
+                if index[w] == UNVISITED {
+                    visiting.push((target, 0));
+                } else if on_stack[w] {
+                    lowlink[v] = lowlink[v].min(index[w]);
+                }
+                continue;
+            }
+
+            // Every link of the page has been followed
+            visiting.pop();
+            if lowlink[v] == index[v] {
+                let c = components.count() as u32;
+                loop {
+                    let member = stack.pop().unwrap();
+                    on_stack[member as usize] = false;
+                    components.component[member as usize] = c;
+                    components.members.push(member);
+                    if member == page {
+                        break;
+                    }
+                }
+                components.starts.push(components.members.len());
+            }
+            if let Some(&(parent, _)) = visiting.last() {
+                let u = parent as usize;
+                lowlink[u] = lowlink[u].min(lowlink[v]);
+            }
+        }
+    }
+    components
+}
+
+/* Finds the strongly connected components of the link graph, stores the component of every page in PAGE_STATS and
+works out the bow tie around the giant component. Path searches use the components to rule out pairs of pages that
+can't have a path between them without searching. Reseeding drops PAGE_STATS, so it has to be run again after
+every seed */
+pub fn compute_components(db_path: &str, isolated_limit: usize) -> rusqlite::Result<ComponentsSummary> {
+    let start = Instant::now();
+    let mut conn = Connection::open(db_path)?;
+    let graph = pagerank::load_graph(&conn)?;
+    let components = tarjan(&graph);
+    let count = components.count();
+    eprintln!("Found {} components in {:?}", count, start.elapsed());
+
+    let tx = conn.transaction()?;
+    db_stats::ensure_page_stats_column(&tx, "component", "integer")?;
+    tx.execute_batch(
+        "delete from PAGE_STATS where page_id not in (select id from PAGES);
+        update PAGE_STATS set component = null;",
+    )?;
+    {
+        let mut stmt = tx.prepare(
+            "insert into PAGE_STATS(page_id, component) values (?1, ?2)
+            on conflict(page_id) do update set component = excluded.component",
+        )?;
+        for (&id, &component) in graph.ids.iter().zip(&components.component) {
+            stmt.execute(params![id, component])?;
+        }
+    }
+    tx.commit()?;
+
+    let size = |c: usize| components.size(c);
+    let Some(giant) = components.giant() else {
+        return Ok(ComponentsSummary {
+            pages: 0,
+            components: 0,
+            giant_component: 0,
+            giant_size: 0,
+            in_component: 0,
+            out_component: 0,
+            isolated: 0,
+            isolated_components: Vec::new(),
+            elapsed_ms: start.elapsed().as_millis(),
+        });
+    };
+
+    /* Links go from higher numbered components to lower ones, so going up from the lowest component every component
+    a component links to has already been settled, and going down from the highest every component linking to it */
+    let links_between = |c: usize| {
+        components
+            .members(c)
+            .iter()
+            .flat_map(|&page| graph.links(page as usize))
+            .map(|&target| components.component[target as usize] as usize)
+    };
+    let mut reaches_giant = vec![false; count];
+    for c in giant..count {
+        reaches_giant[c] = c == giant || links_between(c).any(|target| reaches_giant[target]);
+    }
+    let mut reached_from_giant = vec![false; count];
+    reached_from_giant[giant] = true;
+    for c in (0..=giant).rev() {
+        if reached_from_giant[c] {
+            for target in links_between(c) {
+                reached_from_giant[target] = true;
+            }
+        }
+    }
+
+    let sum = |filter: &dyn Fn(usize) -> bool| (0..count).filter(|&c| filter(c)).map(size).sum::<usize>();
+    let in_component = sum(&|c| c != giant && reaches_giant[c]);
+    let out_component = sum(&|c| c != giant && reached_from_giant[c]);
+    let mut isolated: Vec<usize> = (0..count)
+        .filter(|&c| !reaches_giant[c] && !reached_from_giant[c])
+        .collect();
+    let isolated_pages = isolated.iter().map(|&c| size(c)).sum();
+    isolated.sort_by_key(|&c| std::cmp::Reverse(size(c)));
+    let isolated_components = isolated
+        .into_iter()
+        .take(isolated_limit)
+        .map(|c| {
+            Ok(IsolatedComponent {
+                component: c,
+                size: size(c),
+                titles: components
+                    .members(c)
+                    .iter()
+                    .take(ISOLATED_SAMPLE_TITLES)
+                    .map(|&page| page_title(&conn, graph.ids[page as usize]))
+                    .collect::<rusqlite::Result<Vec<String>>>()?,
+            })
+        })
+        .collect::<rusqlite::Result<Vec<IsolatedComponent>>>()?;
+
+    Ok(ComponentsSummary {
+        pages: graph.ids.len(),
+        components: count,
+        giant_component: giant,
+        giant_size: size(giant),
+        in_component,
+        out_component,
+        isolated: isolated_pages,
+        isolated_components,
+        elapsed_ms: start.elapsed().as_millis(),
+    })
+}
+
+fn page_title(conn: &Connection, id: usize) -> rusqlite::Result<String> {
+    conn.prepare_cached("select page_title from PAGES where id = (?1)")?
+        .query_row(params![id], |row| row.get(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // LinkGraph with page i linking to every page in links[i]
+    fn graph(links: &[&[u32]]) -> LinkGraph {
+        let mut offsets = vec![0];
+        let mut targets = Vec::new();
+        for page_links in links {
+            targets.extend_from_slice(page_links);
+            offsets.push(targets.len());
+        }
+        LinkGraph {
+            ids: (1..=links.len()).collect(),
+            offsets,
+            targets,
+        }
+    }
+
+    fn sorted_members(components: &Components, c: usize) -> Vec<u32> {
+        let mut members = components.members(c).to_vec();
+        members.sort();
+        members
+    }
+
+    #[test]
+    fn finds_cycles_and_orders_components_in_reverse_topological_order() {
+        // 0 -> 1 -> 2 -> 0 is one cycle, it links on to the cycle 3 <-> 4, 5 links to itself and nothing else
+        let components = tarjan(&graph(&[&[1], &[2], &[0, 3], &[4], &[3], &[5]]));
+        assert_eq!(components.count(), 3);
+
+        let first = components.component[0] as usize;
+        let second = components.component[3] as usize;
+        let alone = components.component[5] as usize;
+        assert_eq!(sorted_members(&components, first), vec![0, 1, 2]);
+        assert_eq!(sorted_members(&components, second), vec![3, 4]);
+        assert_eq!(sorted_members(&components, alone), vec![5]);
+        assert!(first > second);
+        assert_eq!(components.giant(), Some(first));
+    }
+
+    #[test]
+    fn every_page_of_a_chain_is_its_own_component() {
+        let links: Vec<Vec<u32>> = (1..=100_000u32).map(|next| vec![next]).collect();
+        let mut links: Vec<&[u32]> = links.iter().map(Vec::as_slice).collect();
+        links.push(&[]);
+        let components = tarjan(&graph(&links));
+        assert_eq!(components.count(), links.len());
+        // The end of the chain is completed first
+        assert_eq!(components.component[links.len() - 1], 0);
+        assert_eq!(components.component[0] as usize, links.len() - 1);
+    }
+
+    #[test]
+    fn empty_graph_has_no_components() {
+        let components = tarjan(&graph(&[]));
+        assert_eq!(components.count(), 0);
+        assert_eq!(components.giant(), None);
+    }
+}
@@ -0,0 +1,253 @@
+use rusqlite::{Connection, params};
+use serde::Serialize;
+use std::io::Write;
+
+use crate::degrees::{self, DegreeStats};
+use crate::search::{has_column, has_table};
+use crate::str_utils;
+
+// Indexes created by create_indexes.sql, searches slow to a crawl without them
+const REQUIRED_INDEXES: [&str; 5] = [
+    "idx_page_titles",
+    "idx_links_page_id",
+    "idx_redirects_og_page_titles",
+    "idx_links_link_titles",
+    "idx_redirects_redirect_titles",
+];
+
+// Number of most linked pages listed when no number is given, and the most that can be asked for
+pub const DEFAULT_STATS_TOP: usize = 10;
+pub const MAX_STATS_TOP: usize = 100;
+
+#[derive(Serialize)]
+pub struct LinkedPage {
+    pub title: String,
+    pub in_degree: usize,
+}
+
+// Size of the database and its most linked to pages, degrees is None until the degrees command has been run
+#[derive(Serialize)]
+pub struct DbStats {
+    pub pages: usize,
+    pub links: usize,
+    pub redirects: usize,
+    pub most_linked: Vec<LinkedPage>,
+    pub degrees: Option<DegreeStats>,
+}
+
+/* Counts rows and finds the top most linked to pages. Links to redirects count toward the page they redirect to when
+SUGGESTIONS has been built, otherwise only direct links are counted */
+pub fn stats(db_path: &str, top: usize) -> rusqlite::Result<DbStats> {
+    let conn = Connection::open(db_path)?;
+    let count = |table: &str| -> rusqlite::Result<usize> {
+        conn.query_row(&format!("select count(*) from {}", table), [], |row| row.get(0))
+    };
+    let has_suggestions = has_table(&conn, "SUGGESTIONS")?
+        && conn.query_row("select exists(select 1 from SUGGESTIONS)", [], |row| row.get(0))?;
+    let most_linked_query = if has_suggestions {
+        "select title, in_degree from SUGGESTIONS where redirect_to is null order by in_degree desc limit (?1)"
+    } else {
+        "select link_title, count(*) as count from LINKS group by link_title order by count desc limit (?1)"
+    };
+    let mut stmt = conn.prepare(most_linked_query)?;
+    let most_linked = stmt
+        .query_map(params![top as i64], |row| {
+            Ok(LinkedPage {
+                title: row.get(0)?,
+                in_degree: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<LinkedPage>>>()?;
+    Ok(DbStats {
+        pages: count("PAGES")?,
+        links: count("LINKS")?,
+        redirects: count("REDIRECTS")?,
+        most_linked,
+        degrees: degrees::degree_stats(&conn, top)?,
+    })
+}
+
+#[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    // Something optional is missing, the features that need it won't work
+    Warning,
+    // Searches will fail or give wrong results
+    Error,
+}
+
+#[derive(Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, ok: bool, failed: CheckStatus, detail: String) -> Check {
+        Check {
+            name,
+            status: if ok { CheckStatus::Ok } else { failed },
+            detail,
+        }
+    }
+}
+
+// Checks that the database is complete and usable, the worst status of the checks is the state of the database
+pub fn check(db_path: &str) -> rusqlite::Result<Vec<Check>> {
+    if !std::path::Path::new(db_path).exists() {
+        return Ok(vec![Check::new(
+            "database",
+            false,
+            CheckStatus::Error,
+            format!("{} doesn't exist", db_path),
+        )]);
+    }
+    let conn = Connection::open(db_path)?;
+    let mut checks = Vec::new();
+
+    let integrity: String = conn.query_row("pragma quick_check", [], |row| row.get(0))?;
+    checks.push(Check::new("integrity", integrity == "ok", CheckStatus::Error, integrity));
+
+    let mut has_tables = true;
+    for table in ["PAGES", "LINKS", "REDIRECTS"] {
+        let exists = has_table(&conn, table)?;
+        has_tables &= exists;
+        checks.push(Check::new(
+            "table",
+            exists,
+            CheckStatus::Error,
+            format!("{} {}", table, if exists { "exists" } else { "is missing" }),
+        ));
+    }
+    if !has_tables {
+        return Ok(checks);
+    }
+
+    for index in REQUIRED_INDEXES {
+        let exists = conn
+            .prepare("select 1 from sqlite_master where type = 'index' and name = (?1)")?
+            .exists(params![index])?;
+        checks.push(Check::new(
+            "index",
+            exists,
+            CheckStatus::Error,
+            format!("{} {}", index, if exists { "exists" } else { "is missing" }),
+        ));
+    }
+
+    if has_table(&conn, "SEED_PROGRESS")? {
+        let unfinished: usize = conn.query_row(
+            "select count(*) from SEED_PROGRESS where finished = 0",
+            [],
+            |row| row.get(0),
+        )?;
+        checks.push(Check::new(
+            "seeding",
+            unfinished == 0,
+            CheckStatus::Error,
+            format!("{} sections didn't finish seeding, run resume", unfinished),
+        ));
+    }
+
+    for (table, column, feature) in [
+        ("LINKS", "edge_type", "edge type filters"),
+        ("PAGES", "description", "page descriptions"),
+        ("PAGE_STATS", "pagerank", "pagerank scores"),
+        ("PAGE_STATS", "component", "ruling out pairs without a path before searching"),
+        ("PAGE_STATS", "in_degree", "degree statistics"),
+    ] {
+        let exists = has_column(&conn, table, column)?;
+        checks.push(Check::new(
+            "column",
+            exists,
+            CheckStatus::Warning,
+            format!(
+                "{}.{} {}, needed for {}",
+                table,
+                column,
+                if exists { "exists" } else { "is missing" },
+                feature
+            ),
+        ));
+    }
+    for (table, feature) in [
+        ("SUGGESTIONS", "/suggest"),
+        ("PAGE_SEARCH", "/search"),
+        ("RED_LINKS", "/wanted and red links of pages"),
+        ("CATEGORY_MEMBERS", "categories of pages and the category tree"),
+    ] {
+        let exists = has_table(&conn, table)?;
+        checks.push(Check::new(
+            "table",
+            exists,
+            CheckStatus::Warning,
+            format!("{} {}, needed for {}", table, if exists { "exists" } else { "is missing" }, feature),
+        ));
+    }
+
+    let broken_redirects: usize = conn.query_row(
+        "select count(*) from REDIRECTS r where not exists (select 1 from PAGES p where p.page_title = r.redirect_title)",
+        [],
+        |row| row.get(0),
+    )?;
+    checks.push(Check::new(
+        "redirects",
+        broken_redirects == 0,
+        CheckStatus::Warning,
+        format!("{} redirects point to pages that don't exist", broken_redirects),
+    ));
+    Ok(checks)
+}
+
+/* Makes sure PAGE_STATS has a column for a score computed per page, creating the table or adding the column if
+they're missing. Each command filling PAGE_STATS adds its own column so they can run in any order */
+pub fn ensure_page_stats_column(conn: &Connection, column: &str, sql_type: &str) -> rusqlite::Result<()> {
+    conn.execute_batch("create table if not exists PAGE_STATS (page_id integer not null primary key)")?;
+    if !has_column(conn, "PAGE_STATS", column)? {
+        conn.execute_batch(&format!("alter table PAGE_STATS add column {} {}", column, sql_type))?;
+    }
+    Ok(())
+}
+
+/* Writes every link that leads to a page as source,target,edge_type csv, or as json lines. Links through redirects
+are written with the page the redirect points to. Returns the number of links written */
+pub fn export_links(db_path: &str, json: bool, out: &mut dyn Write) -> rusqlite::Result<usize> {
+    let conn = Connection::open(db_path)?;
+    let edge_type = if has_column(&conn, "LINKS", "edge_type")? {
+        "l.edge_type"
+    } else {
+        "'wikilink'"
+    };
+    let mut stmt = conn.prepare(&format!(
+        "select src.page_title, coalesce(p.page_title, rp.page_title), {}
+        from LINKS l
+        join PAGES src on src.id = l.page_id
+        left join PAGES p on p.page_title = l.link_title
+        left join REDIRECTS r on p.id is null and r.page_title = l.link_title
+        left join PAGES rp on rp.page_title = r.redirect_title
+        where coalesce(p.id, rp.id) is not null
+        order by l.page_id",
+        edge_type
+    ))?;
+    let mut rows = stmt.query([])?;
+    let write_err = |e: std::io::Error| rusqlite::Error::ToSqlConversionFailure(Box::new(e));
+    if !json {
+        writeln!(out, "source,target,edge_type").map_err(write_err)?;
+    }
+    let mut written = 0;
+    while let Some(row) = rows.next()? {
+        let source: String = row.get(0)?;
+        let target: String = row.get(1)?;
+        let edge_type: String = row.get(2)?;
+        let line = if json {
+            serde_json::json!({ "source": source, "target": target, "edge_type": edge_type }).to_string()
+        } else {
+            format!("{},{},{}", str_utils::csv_escape(&source), str_utils::csv_escape(&target), edge_type)
+        };
+        writeln!(out, "{}", line).map_err(write_err)?;
+        written += 1;
+    }
+    Ok(written)
+}
@@ -0,0 +1,183 @@
+use rusqlite::{Connection, params};
+use serde::Serialize;
+use std::io::Write;
+use std::time::Instant;
+
+use crate::db_stats;
+use crate::pagerank;
+use crate::search::has_column;
+use crate::str_utils;
+
+/* Number of pages whose degree falls in low..=high. Bins double in width, 0 and 1 have their own and then come
+2-3, 4-7, 8-15 and so on, so the long tail of heavily linked pages fits in a few of them */
+#[derive(Serialize)]
+pub struct DegreeBin {
+    pub low: usize,
+    pub high: usize,
+    pub pages: usize,
+}
+
+#[derive(Serialize)]
+pub struct PageDegree {
+    pub title: String,
+    pub in_degree: usize,
+    pub out_degree: usize,
+}
+
+/* Degree distribution of the link graph. Degrees count distinct articles, links through redirects count toward the
+article they redirect to and links to pages that don't exist aren't counted. dead_ends are the articles without
+links to other articles and orphans the ones nothing links to, the most linked of each are listed. most_linked lists
+the articles with the most incoming links and hubs the ones with the most outgoing links */
+#[derive(Serialize)]
+pub struct DegreeStats {
+    pub pages: usize,
+    pub links: usize,
+    pub mean_degree: f64,
+    pub max_in_degree: usize,
+    pub max_out_degree: usize,
+    pub dead_ends: usize,
+    pub orphans: usize,
+    pub in_histogram: Vec<DegreeBin>,
+    pub out_histogram: Vec<DegreeBin>,
+    pub most_linked: Vec<PageDegree>,
+    pub hubs: Vec<PageDegree>,
+    pub top_dead_ends: Vec<PageDegree>,
+    pub top_orphans: Vec<PageDegree>,
+}
+
+/* Counts the links into and out of every article and stores them in PAGE_STATS, where stats reads them from.
+Reseeding drops PAGE_STATS, so it has to be run again after every seed */
+pub fn compute_degrees(db_path: &str, top: usize) -> rusqlite::Result<DegreeStats> {
+    let start = Instant::now();
+    let mut conn = Connection::open(db_path)?;
+    let graph = pagerank::load_graph(&conn)?;
+    let mut in_degrees = vec![0usize; graph.ids.len()];
+    for &target in &graph.targets {
+        in_degrees[target as usize] += 1;
+    }
+    eprintln!("Counted the links of {} pages in {:?}", graph.ids.len(), start.elapsed());
+
+    let tx = conn.transaction()?;
+    db_stats::ensure_page_stats_column(&tx, "in_degree", "integer")?;
+    db_stats::ensure_page_stats_column(&tx, "out_degree", "integer")?;
+    tx.execute_batch(
+        "delete from PAGE_STATS where page_id not in (select id from PAGES);
+        update PAGE_STATS set in_degree = null, out_degree = null;",
+    )?;
+    {
+        let mut stmt = tx.prepare(
+            "insert into PAGE_STATS(page_id, in_degree, out_degree) values (?1, ?2, ?3)
+            on conflict(page_id) do update set in_degree = excluded.in_degree, out_degree = excluded.out_degree",
+        )?;
+        for (i, (&id, &in_degree)) in graph.ids.iter().zip(&in_degrees).enumerate() {
+            stmt.execute(params![id, in_degree, graph.links(i).len()])?;
+        }
+    }
+    tx.commit()?;
+    Ok(degree_stats(&conn, top)?.unwrap())
+}
+
+// Degree distribution from the degrees stored by compute_degrees, None if it hasn't been run
+pub fn degree_stats(conn: &Connection, top: usize) -> rusqlite::Result<Option<DegreeStats>> {
+    if !has_column(conn, "PAGE_STATS", "in_degree")? {
+        return Ok(None);
+    }
+    let (pages, links, max_in_degree, max_out_degree, dead_ends, orphans) = conn.query_row(
+        "select count(*), coalesce(sum(out_degree), 0), coalesce(max(in_degree), 0), coalesce(max(out_degree), 0),
+          coalesce(sum(out_degree = 0), 0), coalesce(sum(in_degree = 0), 0)
+        from PAGE_STATS where in_degree is not null",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)),
+    )?;
+    let top_pages = |filter: &str, order: &str| -> rusqlite::Result<Vec<PageDegree>> {
+        conn.prepare(&format!(
+            "select p.page_title, s.in_degree, s.out_degree from PAGE_STATS s join PAGES p on p.id = s.page_id
+            where s.in_degree is not null {} order by {} desc, p.page_title limit (?1)",
+            filter, order
+        ))?
+        .query_map(params![top as i64], |row| {
+            Ok(PageDegree {
+                title: row.get(0)?,
+                in_degree: row.get(1)?,
+                out_degree: row.get(2)?,
+            })
+        })?
+        .collect()
+    };
+    Ok(Some(DegreeStats {
+        pages,
+        links,
+        mean_degree: links as f64 / pages.max(1) as f64,
+        max_in_degree,
+        max_out_degree,
+        dead_ends,
+        orphans,
+        in_histogram: histogram(conn, "in_degree")?,
+        out_histogram: histogram(conn, "out_degree")?,
+        most_linked: top_pages("", "s.in_degree")?,
+        hubs: top_pages("", "s.out_degree")?,
+        top_dead_ends: top_pages("and s.out_degree = 0", "s.in_degree")?,
+        top_orphans: top_pages("and s.in_degree = 0", "s.out_degree")?,
+    }))
+}
+
+// Bins of 0, 1, 2-3, 4-7 and so on up to the one holding the highest degree
+fn histogram(conn: &Connection, column: &str) -> rusqlite::Result<Vec<DegreeBin>> {
+    let mut bins: Vec<DegreeBin> = Vec::new();
+    let mut stmt = conn.prepare(&format!(
+        "select {0}, count(*) from PAGE_STATS where {0} is not null group by {0} order by {0}",
+        column
+    ))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let degree: usize = row.get(0)?;
+        let pages: usize = row.get(1)?;
+        let bin = if degree == 0 { 0 } else { degree.ilog2() as usize + 1 };
+        while bins.len() <= bin {
+            let low = if bins.is_empty() { 0 } else { 1 << (bins.len() - 1) };
+            bins.push(DegreeBin {
+                low,
+                high: (low * 2).max(1) - 1,
+                pages: 0,
+            });
+        }
+        bins[bin].pages += pages;
+    }
+    Ok(bins)
+}
+
+// Writes both histograms as direction,low,high,pages csv
+pub fn write_histogram_csv(stats: &DegreeStats, out: &mut dyn Write) -> std::io::Result<()> {
+    writeln!(out, "direction,low,high,pages")?;
+    for (direction, histogram) in [("in", &stats.in_histogram), ("out", &stats.out_histogram)] {
+        for bin in histogram {
+            writeln!(out, "{},{},{},{}", direction, bin.low, bin.high, bin.pages)?;
+        }
+    }
+    Ok(())
+}
+
+/* Writes the degrees of every article as title,in_degree,out_degree csv. Returns the number of articles written, None
+if compute_degrees hasn't been run */
+pub fn write_degrees_csv(db_path: &str, out: &mut dyn Write) -> rusqlite::Result<Option<usize>> {
+    let conn = Connection::open(db_path)?;
+    if !has_column(&conn, "PAGE_STATS", "in_degree")? {
+        return Ok(None);
+    }
+    let write_err = |e: std::io::Error| rusqlite::Error::ToSqlConversionFailure(Box::new(e));
+    writeln!(out, "title,in_degree,out_degree").map_err(write_err)?;
+    let mut stmt = conn.prepare(
+        "select p.page_title, s.in_degree, s.out_degree from PAGE_STATS s join PAGES p on p.id = s.page_id
+        where s.in_degree is not null order by p.id",
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut written = 0;
+    while let Some(row) = rows.next()? {
+        let title: String = row.get(0)?;
+        let in_degree: usize = row.get(1)?;
+        let out_degree: usize = row.get(2)?;
+        writeln!(out, "{},{},{}", str_utils::csv_escape(&title), in_degree, out_degree).map_err(write_err)?;
+        written += 1;
+    }
+    Ok(Some(written))
+}
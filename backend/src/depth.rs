@@ -0,0 +1,168 @@
+use bit_set::BitSet;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::search::{self, CategoryFilter, EdgeTypes, FoundPath, Graph, Link, Outcome, Unreachable};
+use crate::str_utils;
+
+// Number of farthest pages listed when no limit is given, and the most that can be asked for
+pub const DEFAULT_FARTHEST_LIMIT: usize = 10;
+pub const MAX_FARTHEST_LIMIT: usize = 100;
+
+/* Which way links are followed. Forward finds the pages an article reaches, reverse follows links backwards to find
+the pages that reach it, how hard it is to get to the article */
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Forward,
+    Reverse,
+}
+
+/* A page as far from the start as any page gets, with one of the shortest paths between them. Going forward the
+path leads from the start to the page, in reverse from the page to the start */
+#[derive(Serialize)]
+pub struct FarthestPage {
+    pub title: String,
+    pub path: FoundPath,
+}
+
+/* Distances between a page and every page it can reach, or every page that can reach it in reverse. distances[d] is
+the number of pages d links away, the start page being the only one at 0, and eccentricity the distance of the
+farthest ones. farthest_total counts every page at that distance, farthest lists up to limit of them */
+#[derive(Serialize)]
+pub struct DepthReport {
+    pub title: String,
+    pub page_id: usize,
+    pub direction: Direction,
+    pub reachable: usize,
+    pub eccentricity: usize,
+    pub distances: Vec<usize>,
+    pub farthest_total: usize,
+    pub farthest: Vec<FarthestPage>,
+    pub elapsed_ms: u128,
+}
+
+/* Breadth first search over every page reachable from the page title refers to, or over every page it can be
+reached from in reverse, only following links of the given edge types. With a category filter the search only goes
+through the articles of the category subtree, pages outside it are counted when reached but their links aren't
+followed, the same pages a path search with the filter could end at. title can be a redirect. Fails with
+PageNotFound or CategoryNotFound if the page or the category of the filter doesn't exist */
+pub fn depth_report(
+    db_path: &str,
+    title: &str,
+    direction: Direction,
+    edge_types: EdgeTypes,
+    category: Option<&CategoryFilter>,
+    limit: usize,
+) -> rusqlite::Result<Outcome<DepthReport>> {
+    let search_start = Instant::now();
+    let graph = Graph::open(db_path)?;
+    let Some((start_id, title)) = graph.resolve(&str_utils::normalize_title(title))? else {
+        return Ok(Err(Unreachable::PageNotFound {
+            title: title.to_string(),
+        }));
+    };
+
+    let within = match category {
+        Some(filter) => match graph.category_pages(filter)? {
+            Some(pages) => Some(pages),
+            None => {
+                return Ok(Err(Unreachable::CategoryNotFound {
+                    category: filter.category.clone(),
+                }));
+            }
+        },
+        None => None,
+    };
+
+    // Only the parent of every page is kept, the links making up the example paths are looked up again at the end
+    let mut seen = BitSet::with_capacity(graph.max_page_id()? + 1);
+    let mut parents: HashMap<usize, usize> = HashMap::new();
+    seen.insert(start_id);
+    let mut distances = vec![1];
+    let mut frontier = vec![start_id];
+    loop {
+        let mut next = Vec::new();
+        for &cur_id in &frontier {
+            if cur_id != start_id && within.as_ref().is_some_and(|pages| !pages.contains(&cur_id)) {
+                continue;
+            }
+            for link in neighbours(&graph, cur_id, direction)? {
+                if edge_types.contains(link.edge_type) && seen.insert(link.id) {
+                    parents.insert(link.id, cur_id);
+                    next.push(link.id);
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        distances.push(next.len());
+        frontier = next;
+    }
+
+    let farthest = frontier
+        .iter()
+        .take(limit)
+        .map(|&id| {
+            Ok(FarthestPage {
+                title: graph.page_title(id)?,
+                path: example_path(&graph, &parents, id, direction, edge_types)?,
+            })
+        })
+        .collect::<rusqlite::Result<Vec<FarthestPage>>>()?;
+    Ok(Ok(DepthReport {
+        title,
+        page_id: start_id,
+        direction,
+        reachable: parents.len() + 1,
+        eccentricity: distances.len() - 1,
+        distances,
+        farthest_total: frontier.len(),
+        farthest,
+        elapsed_ms: search_start.elapsed().as_millis(),
+    }))
+}
+
+// Pages linked to from id going forward, pages linking to it in reverse
+fn neighbours(graph: &Graph, id: usize, direction: Direction) -> rusqlite::Result<Vec<Link>> {
+    match direction {
+        Direction::Forward => graph.links(id),
+        Direction::Reverse => graph.incoming_links(id),
+    }
+}
+
+/* Path between the start of the search and id along the parents found by the search, with the redirect and type of
+every link */
+fn example_path(
+    graph: &Graph,
+    parents: &HashMap<usize, usize>,
+    id: usize,
+    direction: Direction,
+    edge_types: EdgeTypes,
+) -> rusqlite::Result<FoundPath> {
+    // Parents lead back to the start, which is where a forward path begins and a reverse one ends
+    let mut ids = vec![id];
+    while let Some(&parent) = parents.get(ids.last().unwrap()) {
+        ids.push(parent);
+    }
+    if direction == Direction::Forward {
+        ids.reverse();
+    }
+    let mut path = FoundPath {
+        pages: vec![graph.page_title(ids[0])?],
+        edge_types: Vec::new(),
+    };
+    for pair in ids.windows(2) {
+        // Going forward this is the link the search followed, in reverse any link between the two pages would do
+        let link = graph
+            .links(pair[0])?
+            .into_iter()
+            .find(|link| link.id == pair[1] && edge_types.contains(link.edge_type))
+            .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        path.pages.push(search::path_entry(link.title, link.redirect.as_ref()));
+        path.edge_types.push(link.edge_type);
+    }
+    Ok(path)
+}
@@ -5,7 +5,6 @@ use std::collections::VecDeque;
 use std::fs::{File, OpenOptions};
 use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
 use std::time::{Duration, Instant};
-use std::usize;
 
 // Takes contents of a file (wikipedia dumps) and breaks it up into <divisons> sections
 // divisions is the number of threads available
@@ -53,15 +52,12 @@ pub fn divide_input(contents_file: File, divisions: Option<usize>) -> Vec<String
     let mut last_section = String::new();
     loop {
         let bytes = file_reader.read_line(&mut last_section);
-        match bytes {
-            Ok(c) => {
-                if c == 0 {
-                    break;
-                } else {
-                    cur_line_count += 1;
-                }
+        if let Ok(c) = bytes {
+            if c == 0 {
+                break;
+            } else {
+                cur_line_count += 1;
             }
-            _ => (),
         }
     }
     assert!(last_section.ends_with("</mediawiki>"));
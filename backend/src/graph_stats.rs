@@ -0,0 +1,297 @@
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use std::time::Instant;
+
+use crate::components;
+use crate::pagerank::{self, LinkGraph};
+use crate::search::has_column;
+use crate::str_utils;
+
+// Defaults of the graph-stats command
+pub const DEFAULT_SAMPLES: usize = 100;
+pub const DEFAULT_MAX_SWEEPS: usize = 200;
+
+// Normal quantile of the 95% confidence interval of the average path length
+const CONFIDENCE_Z: f64 = 1.96;
+
+/* samples is the number of random pages the average path length is measured from, seed picks them. max_sweeps is the
+most breadth first searches spent narrowing the bounds of the diameter */
+pub struct GraphStatsOptions {
+    pub samples: usize,
+    pub seed: u64,
+    pub max_sweeps: usize,
+}
+
+impl GraphStatsOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.samples == 0 {
+            return Err(String::from("samples must be at least 1"));
+        }
+        // The searches both ways from the best linked page and the double sweeps always run
+        if self.max_sweeps < 4 {
+            return Err(String::from("max sweeps must be at least 4"));
+        }
+        Ok(())
+    }
+}
+
+/* Distances within the giant strongly connected component, the only part of the graph where every page can reach
+every other. The diameter, the longest shortest path, lies between diameter_lower and diameter_upper, which are equal
+when it's exact. avg_path_length is the mean shortest path length from the sampled pages to every other page of the
+component, with its 95% confidence interval. It's exact when every page of the component was sampled.
+distances[d] counts the sampled pairs d links apart. seeded_at is when the seed of the database finished, which is
+later than the date of the dump it was seeded from, None for databases seeded before that was recorded */
+#[derive(Serialize)]
+pub struct GraphStats {
+    pub seeded_at: Option<String>,
+    pub pages: usize,
+    pub links: usize,
+    pub giant_size: usize,
+    pub diameter_lower: usize,
+    pub diameter_upper: usize,
+    pub sweeps: usize,
+    pub samples: usize,
+    pub avg_path_length: f64,
+    pub avg_path_ci_low: f64,
+    pub avg_path_ci_high: f64,
+    pub distances: Vec<u64>,
+    pub elapsed_ms: u128,
+}
+
+/* Breadth first searches restricted to the pages of one component. Shortest paths between two pages of a strongly
+connected component never leave it, so the other pages can be ignored */
+struct Bfs<'a> {
+    forward: &'a LinkGraph,
+    backward: LinkGraph,
+    in_giant: Vec<bool>,
+    distance: Vec<u32>,
+    queue: Vec<u32>,
+}
+
+// Pages reached by a search and how far the farthest of them is
+struct Sweep {
+    eccentricity: usize,
+    farthest: u32,
+    distances: Vec<u64>,
+}
+
+impl Bfs<'_> {
+    fn run(&mut self, start: u32, backward: bool) -> Sweep {
+        let graph = if backward { &self.backward } else { self.forward };
+        for &page in &self.queue {
+            self.distance[page as usize] = u32::MAX;
+        }
+        self.queue.clear();
+        self.queue.push(start);
+        self.distance[start as usize] = 0;
+        let mut distances = vec![1];
+        let mut head = 0;
+        while head < self.queue.len() {
+            let page = self.queue[head];
+            head += 1;
+            let d = self.distance[page as usize] + 1;
+            for &target in graph.links(page as usize) {
+                let t = target as usize;
+                if self.in_giant[t] && self.distance[t] == u32::MAX {
+                    self.distance[t] = d;
+                    self.queue.push(target);
+                    if distances.len() <= d as usize {
+                        distances.push(0);
+                    }
+                    distances[d as usize] += 1;
+                }
+            }
+        }
+        Sweep {
+            eccentricity: distances.len() - 1,
+            farthest: *self.queue.last().unwrap(),
+            distances,
+        }
+    }
+
+    // Pages of the component grouped by their distance from the start of the last search
+    fn levels(&self) -> Vec<Vec<u32>> {
+        let mut levels: Vec<Vec<u32>> = Vec::new();
+        for &page in &self.queue {
+            let d = self.distance[page as usize] as usize;
+            if levels.len() <= d {
+                levels.push(Vec::new());
+            }
+            levels[d].push(page);
+        }
+        levels
+    }
+}
+
+/* Measures how far apart pages of the giant component are and adds the results to GRAPH_STATS, which keeps every run
+so snapshots can be compared. The diameter is bounded with iFUB adapted to directed graphs: searches both ways from
+the best linked page give an upper bound, double sweeps from the pages farthest from it a lower bound, and pages are
+then searched from level by level going inwards until the bounds meet or max_sweeps runs out. Returns None if the
+database has no pages */
+pub fn compute_graph_stats(db_path: &str, options: &GraphStatsOptions) -> rusqlite::Result<Option<GraphStats>> {
+    let start = Instant::now();
+    let conn = Connection::open(db_path)?;
+    let graph = pagerank::load_graph(&conn)?;
+    let components = components::tarjan(&graph);
+    eprintln!("Loaded {} pages and found their components in {:?}", graph.ids.len(), start.elapsed());
+
+    let Some(giant) = components.giant() else {
+        return Ok(None);
+    };
+    let members = components.members(giant);
+    let mut in_giant = vec![false; graph.ids.len()];
+    for &page in members {
+        in_giant[page as usize] = true;
+    }
+    let mut bfs = Bfs {
+        forward: &graph,
+        backward: graph.reversed(),
+        in_giant,
+        distance: vec![u32::MAX; graph.ids.len()],
+        queue: Vec::new(),
+    };
+
+    let (lower, upper, sweeps) = diameter(&mut bfs, members, options.max_sweeps);
+    eprintln!("Diameter between {} and {} after {} searches", lower, upper, sweeps);
+
+    // Pages are sampled without repeats in the order of their hash, so the same seed always picks the same ones
+    let mut sampled = members.to_vec();
+    sampled.sort_by_cached_key(|&page| str_utils::seeded_hash(&graph.ids[page as usize].to_string(), options.seed));
+    sampled.truncate(options.samples);
+    let mut distances: Vec<u64> = Vec::new();
+    let mut means = Vec::with_capacity(sampled.len());
+    for &page in &sampled {
+        let sweep = bfs.run(page, false);
+        let mut total = 0;
+        for (d, &count) in sweep.distances.iter().enumerate() {
+            if distances.len() <= d {
+                distances.push(0);
+            }
+            distances[d] += count;
+            total += d as u64 * count;
+        }
+        means.push(total as f64 / (members.len() - 1).max(1) as f64);
+    }
+    // The start of every search is at distance 0 from itself, which isn't a pair
+    distances[0] = 0;
+
+    let k = means.len() as f64;
+    let avg_path_length = means.iter().sum::<f64>() / k;
+    let margin = if sampled.len() == members.len() || sampled.len() < 2 {
+        0.0
+    } else {
+        let variance = means.iter().map(|mean| (mean - avg_path_length).powi(2)).sum::<f64>() / (k - 1.0);
+        CONFIDENCE_Z * (variance / k).sqrt()
+    };
+
+    let stats = GraphStats {
+        seeded_at: seeded_at(&conn)?,
+        pages: graph.ids.len(),
+        links: graph.targets.len(),
+        giant_size: members.len(),
+        diameter_lower: lower,
+        diameter_upper: upper,
+        sweeps,
+        samples: sampled.len(),
+        avg_path_length,
+        avg_path_ci_low: avg_path_length - margin,
+        avg_path_ci_high: avg_path_length + margin,
+        distances,
+        elapsed_ms: start.elapsed().as_millis(),
+    };
+    store(&conn, &stats)?;
+    Ok(Some(stats))
+}
+
+/* Lower and upper bound of the diameter and the number of searches spent on them. Every shortest path from x to y is
+at most as long as going through the pivot u, d(x, u) + d(u, y). Once the pages more than i links after u have been
+searched backwards and the ones more than i links before it forwards, every pair not covered by those searches is
+at most 2i apart */
+fn diameter(bfs: &mut Bfs, members: &[u32], max_sweeps: usize) -> (usize, usize, usize) {
+    let degree = |page: u32| bfs.forward.links(page as usize).len() + bfs.backward.links(page as usize).len();
+    let pivot = *members.iter().max_by_key(|&&page| degree(page)).unwrap();
+
+    let from_pivot = bfs.run(pivot, false);
+    let after = bfs.levels();
+    let to_pivot = bfs.run(pivot, true);
+    let before = bfs.levels();
+    let mut lower = from_pivot.eccentricity.max(to_pivot.eccentricity);
+    let mut upper = from_pivot.eccentricity + to_pivot.eccentricity;
+    let mut sweeps = 2;
+
+    // Double sweeps, the pages farthest from the pivot tend to be close to the ends of the diameter
+    lower = lower.max(bfs.run(from_pivot.farthest, true).eccentricity);
+    lower = lower.max(bfs.run(to_pivot.farthest, false).eccentricity);
+    sweeps += 2;
+
+    for i in (0..after.len().max(before.len())).rev() {
+        upper = upper.min(lower.max(2 * i));
+        if lower >= upper {
+            break;
+        }
+        let level = after.get(i).into_iter().flatten().map(|&page| (page, true));
+        let level = level.chain(before.get(i).into_iter().flatten().map(|&page| (page, false)));
+        for (page, backward) in level {
+            if sweeps == max_sweeps {
+                return (lower, upper, sweeps);
+            }
+            lower = lower.max(bfs.run(page, backward).eccentricity);
+            sweeps += 1;
+        }
+    }
+    (lower, upper.max(lower), sweeps)
+}
+
+// When the last section of the seed finished
+fn seeded_at(conn: &Connection) -> rusqlite::Result<Option<String>> {
+    if !has_column(conn, "SEED_PROGRESS", "finished_at")? {
+        return Ok(None);
+    }
+    conn.query_row("select max(finished_at) from SEED_PROGRESS", [], |row| row.get(0))
+        .optional()
+        .map(Option::flatten)
+}
+
+/* GRAPH_STATS isn't dropped when reseeding, it's the history of the measurements across snapshots. Tables created
+before seeded_at was named that call it snapshot_date */
+fn store(conn: &Connection, stats: &GraphStats) -> rusqlite::Result<()> {
+    if has_column(conn, "GRAPH_STATS", "snapshot_date")? {
+        conn.execute_batch("alter table GRAPH_STATS rename column snapshot_date to seeded_at")?;
+    }
+    conn.execute_batch(
+        "create table if not exists GRAPH_STATS (
+          id integer not null primary key,
+          computed_at text not null,
+          seeded_at text,
+          pages integer not null,
+          links integer not null,
+          giant_size integer not null,
+          diameter_lower integer not null,
+          diameter_upper integer not null,
+          sweeps integer not null,
+          samples integer not null,
+          avg_path_length real not null,
+          avg_path_ci_low real not null,
+          avg_path_ci_high real not null
+        )",
+    )?;
+    conn.execute(
+        "insert into GRAPH_STATS(computed_at, seeded_at, pages, links, giant_size, diameter_lower, diameter_upper,
+          sweeps, samples, avg_path_length, avg_path_ci_low, avg_path_ci_high)
+        values (datetime('now'), ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            stats.seeded_at,
+            stats.pages,
+            stats.links,
+            stats.giant_size,
+            stats.diameter_lower,
+            stats.diameter_upper,
+            stats.sweeps,
+            stats.samples,
+            stats.avg_path_length,
+            stats.avg_path_ci_low,
+            stats.avg_path_ci_high,
+        ],
+    )?;
+    Ok(())
+}
@@ -1,47 +1,185 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs::remove_file;
-use std::io::Write;
-use std::sync::{Arc, Mutex};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread::{self, available_parallelism};
 use std::time::Instant;
-use std::usize;
 
-use actix_web::{App, HttpResponse, HttpServer, Responder, get, web};
+use actix_web::{App, HttpMessage, HttpRequest, HttpResponse, HttpServer, Responder, get, post, web};
+use clap::Parser;
 use quick_xml::events::Event;
 use quick_xml::reader::Reader;
 use regex::RegexBuilder;
 use rusqlite::{params, Connection};
 use serde::Deserialize;
-use bit_set::BitSet;
 
+mod batch;
+mod cache;
+mod categories;
+mod cli;
+mod components;
+mod db_stats;
+mod degrees;
+mod depth;
 mod file_utils;
+mod graph_stats;
+mod page;
+mod pagerank;
+mod shell;
+mod search;
 mod str_utils;
 
+use search::{CategoryFilter, EdgeType, EdgeTypes, FoundPath, PathMode, SearchOptions, SearchProgress, Unreachable};
+
+// Database used when no other path is given with --db
+const DEFAULT_DB_PATH: &str = "main.db";
+
+// Namespace id of category pages in the dump, articles are namespace 0
+const CATEGORY_NAMESPACE: i32 = 14;
+// Marks maintenance categories like "Articles with short description" that Wikipedia doesn't show readers
+const HIDDEN_CATEGORY_MAGIC_WORD: &str = "__HIDDENCAT__";
+// Events /path/stream holds for a slow client before the search waits for it to catch up
+const STREAM_BUFFER: usize = 64;
+
+// Database every command works with, set once from the command line before anything opens it
+static DB_PATH: OnceLock<String> = OnceLock::new();
+
+fn db_path() -> &'static str {
+    DB_PATH.get().map_or(DEFAULT_DB_PATH, String::as_str)
+}
+
+lazy_static::lazy_static! {
+    // Results of recent shortest path searches, shared by the server and the cli
+    static ref PATH_CACHE: cache::QueryCache<PathKey, PathResult> =
+        cache::QueryCache::new(cache::capacity_from_env());
+}
+
 #[derive(Debug)]
 enum State {
-    IDLE,
-    TITLE,
-    IGNORE,
-    TEXT,
-    NAMESPACE,
+    Idle,
+    Title,
+    Ignore,
+    Text,
+    Namespace,
 }
 
 #[derive(Deserialize)]
 struct ShortestPathQueryParams {
     startpage: String,
     endpage: String,
+    #[serde(default)]
+    all: bool,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl ShortestPathQueryParams {
+    fn mode(&self) -> PathMode {
+        if self.all {
+            PathMode::All {
+                offset: self.offset,
+                limit: self
+                    .limit
+                    .unwrap_or(search::DEFAULT_PATH_LIMIT)
+                    .min(search::MAX_PATH_LIMIT),
+            }
+        } else {
+            PathMode::Single
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct KShortestPathsQueryParams {
+    startpage: String,
+    endpage: String,
+    k: usize,
+}
+
+#[derive(Deserialize)]
+struct BacklinksQueryParams {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct RelatedQueryParams {
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct WantedQueryParams {
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct CategoryQueryParams {
+    depth: Option<usize>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct CategoryPathQueryParams {
+    startpage: String,
+    endpage: String,
+}
+
+#[derive(Deserialize)]
+struct StatsQueryParams {
+    top: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct TextSearchQueryParams {
+    q: String,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct SuggestQueryParams {
+    q: String,
+    limit: Option<usize>,
+}
+
+// Key for PATH_CACHE, titles are normalized so that "cat" and "Cat" share an entry
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct PathKey {
+    start: String,
+    end: String,
+    mode: PathMode,
+    options: SearchOptions,
+}
+
+// Value stored in PATH_CACHE, either the paths found or why there aren't any
+#[derive(Clone)]
+enum PathResult {
+    Single(search::Outcome<FoundPath>),
+    All(search::Outcome<search::AllShortestPaths>),
+    KShortest(search::Outcome<Vec<FoundPath>>),
 }
 
 fn parse_and_write_db(
-    thread_id: usize,
+    _thread_id: usize,
     contents: &str,
     db_conn: Arc<Mutex<Connection>>,
     lang_map: HashMap<String, String>,
 ) -> rusqlite::Result<()> {
     // HashMap to store stuff in memory until written to database
-    let mut pages_to_links: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut pages_to_links: HashMap<String, HashSet<(String, EdgeType)>> = HashMap::new();
     let mut redirects_to_pages: HashMap<String, String> = HashMap::new();
+    let mut descriptions: HashMap<String, String> = HashMap::new();
+    let mut disambiguations: HashSet<String> = HashSet::new();
+    // Categories of every article, and the parent categories of every category page
+    let mut page_categories: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut category_parents: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut hidden_categories: HashSet<String> = HashSet::new();
 
     // Regex to find internal wikipedia links and links to language pages
     // Internal wikipedia links look like [[text]], language links look like {{etymology|<language code>
@@ -54,9 +192,11 @@ fn parse_and_write_db(
     .unwrap();
 
     // xml reader object
-    let mut reader = Reader::from_str(&contents);
+    let mut reader = Reader::from_str(contents);
     let mut cur_page = String::default();
-    let mut cur_state: State = State::IDLE;
+    let mut cur_state: State = State::Idle;
+    // Name of the category when the current page is a category page
+    let mut cur_category: Option<String> = None;
     let mut count: usize = 0;
     let start = Instant::now();
     loop {
@@ -75,36 +215,36 @@ fn parse_and_write_db(
             A self-closed redirect tag indicates that that revision just modified a link to redirect to another article
             We don't care about those. We want pages that don't contain a redirect tag, but because redirect tags always
             come before text tags that contain actual content, we need to check if a redirect came before. That's why
-            State::IGNORE is set whenever encountering a self-closing redirect tag */
+            State::Ignore is set whenever encountering a self-closing redirect tag */
             Ok(Event::Start(e)) => match e.name().as_ref() {
-                b"title" => cur_state = State::TITLE,
+                b"title" => cur_state = State::Title,
                 b"text" => match cur_state {
-                    State::IGNORE => (),
-                    _ => cur_state = State::TEXT,
+                    State::Ignore => (),
+                    _ => cur_state = State::Text,
                 },
-                b"ns" => cur_state = State::NAMESPACE,
+                b"ns" => cur_state = State::Namespace,
                 _ => (),
             },
-            Ok(Event::Empty(e)) => match e.name().as_ref() {
-                b"redirect" => {
-                    cur_state = State::IGNORE;
-                    pages_to_links.remove(&cur_page);
-                    if let Some(attribute) = e.attributes().next() {
-                        let redirect_title = String::from(
-                            attribute
-                                .unwrap()
-                                .decode_and_unescape_value(&reader)
-                                .unwrap(),
-                        );
-                        redirects_to_pages.insert(cur_page.clone(), redirect_title);
-                    }
+            Ok(Event::Empty(e)) if e.name().as_ref() == b"redirect" => {
+                cur_state = State::Ignore;
+                pages_to_links.remove(&cur_page);
+                if let Some(category) = cur_category.take() {
+                    category_parents.remove(&category);
                 }
-                _ => (),
-            },
+                if let Some(attribute) = e.attributes().next() {
+                    let redirect_title = String::from(
+                        attribute
+                            .unwrap()
+                            .decode_and_unescape_value(&reader)
+                            .unwrap(),
+                    );
+                    redirects_to_pages.insert(cur_page.clone(), redirect_title);
+                }
+            }
 
             /* This event handles all text within the dump file. We only really want to handle text in a few cases.
-            Either cur_state is State::TITLE (Meaning we just saw a title tag) or State::NAMESPACE (We just saw a namespace tag)
-            or State::TEXT (We've hit a text tag and our cur_state is not State::IGNORE) . In the former, the text that we read will
+            Either cur_state is State::Title (Meaning we just saw a title tag) or State::Namespace (We just saw a namespace tag)
+            or State::Text (We've hit a text tag and our cur_state is not State::Ignore) . In the former, the text that we read will
             be the name of the page, in the second case its the namespace id and in the latter, the text will be the actual content on that page. When reading the content on
             the page, we use the links_regex to capture all links to other wikipedia articles. Those links will appear as text surrounded
             by [[ ]], so the link to Canada will be [[Canada]]. Links might also be a part of a sentence and so might not be exactly the
@@ -120,43 +260,83 @@ fn parse_and_write_db(
 
             Another thing to mention is Wikipedia namespaces. A namespace is an identifier for a wikipedia page that categorizes it as one of 28 types. One
             of these types are normal wikipedia articles but there are also pages for files, help, drafts, and others. We're only concerned with actual
-            Wikipedia articles who namespace id is 0, everything else we'll ignore. If we see a namespace tag <ns>, cur_state is set to State::NAMESPACE to
-            read the namespace id as text. If the namespace id is anything else but 0, we set cur_state to State::IGNORE similarly to how its done for redirects
+            Wikipedia articles who namespace id is 0, everything else we'll ignore. If we see a namespace tag <ns>, cur_state is set to State::Namespace to
+            read the namespace id as text. If the namespace id is anything else but 0, we set cur_state to State::Ignore similarly to how its done for redirects.
+            The exception is category pages, namespace 14. Their text is read only for the [[Category:...]] links that make them subcategories of other
+            categories, and the same links in articles are kept as the categories the article is in
 
             Namespaces also have their own internal link structure, so the link regex also checks to make sure that we're not capturing those as well
             */
             Ok(Event::Text(e)) => match cur_state {
-                State::TITLE => {
-                    cur_page = String::from(e.unescape().unwrap().into_owned());
+                State::Title => {
+                    cur_page = e.unescape().unwrap().into_owned();
                     pages_to_links.insert(cur_page.clone(), HashSet::new());
-                    cur_state = State::IDLE;
+                    cur_category = None;
+                    cur_state = State::Idle;
                 }
-                State::NAMESPACE => {
-                    let ns_num: i32 = String::from(e.unescape().unwrap().into_owned())
+                State::Namespace => {
+                    let ns_num: i32 = e.unescape().unwrap().into_owned()
                         .parse()
                         .unwrap();
-                    if ns_num != 0 {
-                        cur_state = State::IGNORE;
+                    if ns_num == CATEGORY_NAMESPACE {
+                        pages_to_links.remove(&cur_page);
+                        cur_category = str_utils::category_name(&cur_page);
+                        cur_state = match &cur_category {
+                            Some(category) => {
+                                category_parents.insert(category.clone(), HashSet::new());
+                                State::Idle
+                            }
+                            // A title without a usable category name is skipped like any other namespace
+                            None => State::Ignore,
+                        };
+                    } else if ns_num != 0 {
+                        cur_state = State::Ignore;
                         pages_to_links.remove(&cur_page);
                     } else {
-                        cur_state = State::IDLE;
+                        cur_state = State::Idle;
                     }
                 }
-                State::TEXT => {
+                State::Text if cur_category.is_some() => {
+                    let cur_text = e.unescape().unwrap().into_owned();
+                    let category = cur_category.as_ref().unwrap();
+                    if cur_text.contains(HIDDEN_CATEGORY_MAGIC_WORD) {
+                        hidden_categories.insert(category.clone());
+                    }
+                    let parents = category_parents.get_mut(category).unwrap();
+                    for cap in links_regex.captures_iter(&cur_text) {
+                        if let Some(parent) =
+                            cap.get(1).and_then(|val| str_utils::category_name(&val.as_str()[2..val.len() - 2]))
+                        {
+                            parents.insert(parent);
+                        }
+                    }
+                    cur_state = State::Idle;
+                }
+                State::Text => {
                     count += 1;
-                    let cur_text = String::from(e.unescape().unwrap().into_owned());
+                    let cur_text = e.unescape().unwrap().into_owned();
+                    if let Some(description) = str_utils::extract_description(&cur_text) {
+                        descriptions.insert(cur_page.clone(), description);
+                    }
+                    if str_utils::is_disambiguation(&cur_page, &cur_text) {
+                        disambiguations.insert(cur_page.clone());
+                    }
                     let captures = links_regex.captures_iter(&cur_text);
                     for cap in captures {
                         if let Some(val) = cap.get(1) {
                             let name_slice = &val.as_str()[2..val.len() - 2];
+                            if let Some(category) = str_utils::category_name(name_slice) {
+                                page_categories.entry(cur_page.clone()).or_default().insert(category);
+                            }
                             if let Some(article_name) = str_utils::process_article_name(name_slice)
                             {
                                 pages_to_links
                                     .get_mut(&cur_page)
                                     .unwrap()
-                                    .insert(String::from(str_utils::capitalize_first_char(
-                                        article_name,
-                                    )));
+                                    .insert((
+                                        str_utils::capitalize_first_char(article_name),
+                                        EdgeType::Wikilink,
+                                    ));
                             }
                         }
                         if let Some(val) = cap.get(2) {
@@ -165,11 +345,11 @@ fn parse_and_write_db(
                                 pages_to_links
                                     .get_mut(&cur_page)
                                     .unwrap()
-                                    .insert(lang_name.clone());
+                                    .insert((lang_name.clone(), EdgeType::Etymology));
                             }
                         }
                     }
-                    cur_state = State::IDLE;
+                    cur_state = State::Idle;
                 }
                 _ => (),
             },
@@ -193,14 +373,18 @@ fn parse_and_write_db(
 
         // Prepared statements to insert a page title into the PAGES table and get the id from the page after its inserted
         let mut page_title_insert = connection
-            .prepare("insert or ignore into PAGES(page_title) values(?1);")
+            .prepare("insert or ignore into PAGES(page_title, description, disambiguation) values(?1, ?2, ?3);")
             .unwrap();
         let mut get_last_id = connection
             .prepare("select id from PAGES where page_title = (?1);")
             .unwrap();
 
         // Insert the current page title, get its id in the pages database
-        page_title_insert.execute(params![page_title])?;
+        page_title_insert.execute(params![
+            page_title,
+            descriptions.get(&page_title),
+            disambiguations.contains(&page_title)
+        ])?;
         let last_id: i64 = get_last_id.query_row(params![page_title], |row| row.get(0))?;
 
         drop(page_title_insert);
@@ -208,12 +392,12 @@ fn parse_and_write_db(
 
         let insert_page_title_tx = connection.transaction().unwrap();
 
-        let mut insert_page_stmt = (&insert_page_title_tx)
-            .prepare("insert into LINKS(page_id, link_title) values(?1, ?2);")
+        let mut insert_page_stmt = insert_page_title_tx
+            .prepare("insert into LINKS(page_id, link_title, edge_type) values(?1, ?2, ?3);")
             .unwrap();
 
-        for link in links {
-            let res = insert_page_stmt.execute(params![last_id, link]);
+        for (link, edge_type) in links {
+            let res = insert_page_stmt.execute(params![last_id, link, edge_type.as_str()]);
             match res {
                 Ok(_) => (),
                 Err(e) => eprintln!(
@@ -224,6 +408,16 @@ fn parse_and_write_db(
         }
         drop(insert_page_stmt);
 
+        let mut insert_category_stmt = insert_page_title_tx
+            .prepare("insert into CATEGORY_MEMBERS(page_id, category) values(?1, ?2);")
+            .unwrap();
+        for category in page_categories.remove(&page_title).unwrap_or_default() {
+            if let Err(e) = insert_category_stmt.execute(params![last_id, category]) {
+                eprintln!("Error inserting category {} for page {}: {}", category, page_title, e);
+            }
+        }
+        drop(insert_category_stmt);
+
         let res = insert_page_title_tx.commit();
         match res {
             Ok(_) => (),
@@ -231,7 +425,7 @@ fn parse_and_write_db(
         }
     }
     let insert_redirects_tx = connection.transaction().unwrap();
-    let mut insert_redirects_stmt = (&insert_redirects_tx)
+    let mut insert_redirects_stmt = insert_redirects_tx
         .prepare("insert into REDIRECTS(page_title, redirect_title) values (?1, ?2)")
         .unwrap();
 
@@ -257,11 +451,48 @@ fn parse_and_write_db(
         Err(e) => eprintln!("Error inserting redirects: {}", e),
     }
 
+    // Category pages and the categories they're subcategories of
+    let insert_categories_tx = connection.transaction().unwrap();
+    {
+        let mut insert_category_stmt = insert_categories_tx
+            .prepare("insert or ignore into CATEGORIES(title, hidden) values (?1, ?2)")
+            .unwrap();
+        let mut get_category_id = insert_categories_tx
+            .prepare("select id from CATEGORIES where title = (?1)")
+            .unwrap();
+        let mut insert_parent_stmt = insert_categories_tx
+            .prepare("insert or ignore into SUBCATEGORIES(category_id, parent) values (?1, ?2)")
+            .unwrap();
+        for (category, parents) in category_parents {
+            insert_category_stmt.execute(params![category, hidden_categories.contains(&category)])?;
+            let category_id: i64 = get_category_id.query_row(params![category], |row| row.get(0))?;
+            for parent in parents {
+                if let Err(e) = insert_parent_stmt.execute(params![category_id, parent]) {
+                    eprintln!("Error inserting parent category {} of {}: {}", parent, category, e);
+                }
+            }
+        }
+    }
+    if let Err(e) = insert_categories_tx.commit() {
+        eprintln!("Error inserting categories: {}", e);
+    }
+
     drop(connection);
     Ok(())
 }
 
-fn seed_db() -> rusqlite::Result<()> {
+// Whether seeding builds the full text search index, WIKIMAPIA_SEARCH_INDEX=0 skips it
+fn search_index_from_env() -> bool {
+    match env::var("WIKIMAPIA_SEARCH_INDEX") {
+        Ok(val) => !matches!(val.trim(), "0" | "false"),
+        Err(_) => true,
+    }
+}
+
+/* Downloads every section of the latest dump and writes its pages, links and redirects to the database. With
+resume the tables are kept and sections that were already seeded are skipped, a section that was interrupted is
+removed and seeded again */
+fn seed_db(resume: bool) -> rusqlite::Result<()> {
     let total_time_start = Instant::now();
     let files_to_download = file_utils::get_wikipedia_dumps().unwrap();
     let num_sections = files_to_download.len();
@@ -272,14 +503,22 @@ fn seed_db() -> rusqlite::Result<()> {
     by threads */
     let mut sections_processed = 0;
     let num_cpus = available_parallelism().unwrap().get();
-    let db_path = "main.db";
+    let db_path = db_path();
 
     let setup_connection = Connection::open(db_path).unwrap();
     let conn_ref = &setup_connection;
 
-    let create_tables = std::fs::read_to_string("create_tables.sql").unwrap();
+    let mut finished_sections: HashSet<String> = HashSet::new();
+    if resume {
+        upgrade_schema(conn_ref)?;
+        stale_stats_removed(conn_ref)?;
+        finished_sections = unfinished_sections_removed(conn_ref)?;
+        println!("Resuming, {} sections were already seeded", finished_sections.len());
+    } else {
+        let create_tables = std::fs::read_to_string("create_tables.sql").unwrap();
+        conn_ref.execute_batch(&create_tables).unwrap();
+    }
     let language_codes = std::fs::read_to_string("language_codes.sql").unwrap();
-    conn_ref.execute_batch(&create_tables).unwrap();
     conn_ref.execute_batch(&language_codes).unwrap();
 
     let mut lang_map: HashMap<String, String> = HashMap::new();
@@ -300,7 +539,17 @@ fn seed_db() -> rusqlite::Result<()> {
         // if section != "enwiki-latest-pages-articles12.xml-p8554860p9172788.bz2" {
         //     continue;
         // }
+        if finished_sections.contains(&section) {
+            sections_processed += 1;
+            continue;
+        }
         let section_time_start = Instant::now();
+        conn_ref.execute(
+            "insert into SEED_PROGRESS(section, first_page_id, first_redirect_id, first_category_id)
+            values (?1, (select coalesce(max(id), 0) + 1 from PAGES), (select coalesce(max(id), 0) + 1 from REDIRECTS),
+                (select coalesce(max(id), 0) + 1 from CATEGORIES))",
+            params![section],
+        )?;
         let contents_file = file_utils::download_decompress_save_to_file(&section).unwrap();
 
         let connection = Connection::open(db_path).unwrap();
@@ -315,7 +564,7 @@ fn seed_db() -> rusqlite::Result<()> {
             let conn_clone = Arc::clone(&conn_mutex);
             let lang_map_clone = lang_map.clone();
             let handle = thread::spawn(move || {
-                parse_and_write_db(i, &group.as_str(), conn_clone, lang_map_clone)
+                parse_and_write_db(i, group.as_str(), conn_clone, lang_map_clone)
             });
             handles.push(handle);
         }
@@ -327,6 +576,10 @@ fn seed_db() -> rusqlite::Result<()> {
             });
         }
 
+        conn_ref.execute(
+            "update SEED_PROGRESS set finished = 1, finished_at = datetime('now') where section = (?1)",
+            params![section],
+        )?;
         let section_time_end = section_time_start.elapsed();
         sections_processed += 1;
         println!(
@@ -338,6 +591,16 @@ fn seed_db() -> rusqlite::Result<()> {
     let total_time_end = total_time_start.elapsed();
     let create_indexes = std::fs::read_to_string("create_indexes.sql").unwrap();
     conn_ref.execute_batch(&create_indexes).unwrap();
+    let create_suggestions = std::fs::read_to_string("create_suggestions.sql").unwrap();
+    conn_ref.execute_batch(&create_suggestions).unwrap();
+    let create_red_links = std::fs::read_to_string("create_red_links.sql").unwrap();
+    conn_ref.execute_batch(&create_red_links).unwrap();
+    if search_index_from_env() {
+        let create_search_index = std::fs::read_to_string("create_search_index.sql").unwrap();
+        conn_ref.execute_batch(&create_search_index).unwrap();
+    }
+    // Any paths found before reseeding may no longer exist
+    PATH_CACHE.clear();
     println!(
         "Processing all Wikipedia sections took: {:?}",
         total_time_end
@@ -345,8 +608,95 @@ fn seed_db() -> rusqlite::Result<()> {
     Ok(())
 }
 
+/* Adds what databases seeded by older versions are missing, so resuming them can write everything a seed writes now.
+Sections seeded before that stay without it */
+fn upgrade_schema(conn: &Connection) -> rusqlite::Result<()> {
+    for (table, column, definition) in [
+        ("SEED_PROGRESS", "finished_at", "text"),
+        ("SEED_PROGRESS", "first_category_id", "integer not null default 1"),
+        ("PAGES", "disambiguation", "integer not null default 0"),
+    ] {
+        if !search::has_column(conn, table, column)? {
+            conn.execute_batch(&format!("alter table {} add column {} {}", table, column, definition))?;
+        }
+    }
+    conn.execute_batch(
+        "create table if not exists CATEGORIES (
+          id integer not null primary key,
+          title text not null unique,
+          hidden integer not null default 0
+        );
+        create table if not exists CATEGORY_MEMBERS (page_id integer not null, category text not null);
+        create table if not exists SUBCATEGORIES (category_id integer not null, parent text not null);
+        create table if not exists RED_LINKS (title text not null primary key, pages integer not null);",
+    )?;
+    // Pairs repeated by resuming before they were unique are removed so the index can be created
+    let has_unique_pairs = conn
+        .prepare("select 1 from sqlite_master where type = 'index' and name = 'idx_subcategories_pairs'")?
+        .exists([])?;
+    if !has_unique_pairs {
+        conn.execute_batch(
+            "delete from SUBCATEGORIES
+              where rowid not in (select min(rowid) from SUBCATEGORIES group by category_id, parent);
+            create unique index idx_subcategories_pairs on SUBCATEGORIES(category_id, parent);",
+        )?;
+    }
+    Ok(())
+}
+
+/* Removes what was computed from the database before the seed being resumed finished. PAGE_STATS is dropped, the
+sections still to be seeded add pages it has no scores or components for. Measurements in GRAPH_STATS taken since
+the first section of this seed finished describe a partial graph and are removed, older ones measured earlier
+snapshots and are kept */
+fn stale_stats_removed(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch("drop table if exists PAGE_STATS")?;
+    if search::has_table(conn, "GRAPH_STATS")? {
+        conn.execute(
+            "delete from GRAPH_STATS where computed_at >= (select min(finished_at) from SEED_PROGRESS)",
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+/* Removes what was written by sections that didn't finish seeding and returns the sections that did. Fails if the
+database wasn't seeded with progress tracking, there's nothing to resume then */
+fn unfinished_sections_removed(conn: &Connection) -> rusqlite::Result<HashSet<String>> {
+    let mut stmt = conn.prepare(
+        "select section, first_page_id, first_redirect_id, first_category_id, finished from SEED_PROGRESS
+        order by first_page_id",
+    )?;
+    let sections = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, bool>(4)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut finished = HashSet::new();
+    for (section, first_page_id, first_redirect_id, first_category_id, done) in sections {
+        if done {
+            finished.insert(section);
+            continue;
+        }
+        println!("Removing the partially seeded section {}", section);
+        conn.execute("delete from LINKS where page_id >= (?1)", params![first_page_id])?;
+        conn.execute("delete from CATEGORY_MEMBERS where page_id >= (?1)", params![first_page_id])?;
+        conn.execute("delete from PAGES where id >= (?1)", params![first_page_id])?;
+        conn.execute("delete from REDIRECTS where id >= (?1)", params![first_redirect_id])?;
+        conn.execute("delete from SUBCATEGORIES where category_id >= (?1)", params![first_category_id])?;
+        conn.execute("delete from CATEGORIES where id >= (?1)", params![first_category_id])?;
+        conn.execute("delete from SEED_PROGRESS where section = (?1)", params![section])?;
+    }
+    Ok(finished)
+}
+
 fn check_for_page(page_name: &str) -> rusqlite::Result<String> {
-    let check_conn = Connection::open("main.db").unwrap();
+    let check_conn = Connection::open(db_path()).unwrap();
     check_conn.query_row(
         "select * from PAGES where page_title = (?1)",
         params![page_name],
@@ -354,286 +704,772 @@ fn check_for_page(page_name: &str) -> rusqlite::Result<String> {
     )
 }
 
-fn find_redirect(page_name: &str) -> rusqlite::Result<String> {
-    let find_redirect_conn = Connection::open("main.db").unwrap();
-    find_redirect_conn.query_row(
-        "select redirect_title from redirects where page_title = (?1)",
-        params![page_name],
-        |row| row.get(0),
-    )
+// Runs the search described by mode, normalizing the titles and serving repeated searches from PATH_CACHE
+fn cached_shortest_path(
+    start_page: &str,
+    end_page: &str,
+    mode: PathMode,
+    options: SearchOptions,
+    on_progress: &mut dyn FnMut(&SearchProgress) -> bool,
+) -> rusqlite::Result<PathResult> {
+    let key = PathKey {
+        start: str_utils::normalize_title(start_page),
+        end: str_utils::normalize_title(end_page),
+        mode,
+        options: options.normalized(),
+    };
+    if let Some(result) = PATH_CACHE.get(&key, db_path()) {
+        eprintln!("Cache hit for {} -> {}", key.start, key.end);
+        return Ok(result);
+    }
+    let result = match key.mode {
+        PathMode::Single => PathResult::Single(search::find_shortest_path(
+            db_path(),
+            &key.start,
+            &key.end,
+            &key.options,
+            on_progress,
+        )?),
+        PathMode::All { offset, limit } => PathResult::All(search::find_all_shortest_paths(
+            db_path(),
+            &key.start,
+            &key.end,
+            offset,
+            limit,
+            &key.options,
+            on_progress,
+        )?),
+        PathMode::KShortest { k } => PathResult::KShortest(search::k_shortest_paths(
+            db_path(),
+            &key.start,
+            &key.end,
+            k,
+            &key.options,
+            on_progress,
+        )?),
+    };
+    PATH_CACHE.insert(key, result.clone(), db_path());
+    Ok(result)
 }
 
-fn find_depth(start_page: &str) -> rusqlite::Result<()> {
-    let search_start = Instant::now();
-    let mut seen: HashMap<String, String> = HashMap::new();
-    seen.insert(start_page.to_string(), start_page.to_string());
-    let mut queue: VecDeque<(String, i32)> = VecDeque::from([(String::from(start_page), 0)]);
-    let mut max_depth = 0;
-
-    let search_conn = Connection::open("main.db").unwrap();
-    let mut get_page_id = search_conn
-        .prepare("select id from PAGES where page_title = (?1)")
-        .unwrap();
-    let mut find_links = search_conn
-        .prepare("select link_title from LINKS where page_id = (?1)")
-        .unwrap();
+// Shortest path between two pages with the default search, through PATH_CACHE
+fn cached_single_path(
+    start_page: &str,
+    end_page: &str,
+    options: &SearchOptions,
+) -> rusqlite::Result<search::Outcome<FoundPath>> {
+    match cached_shortest_path(start_page, end_page, PathMode::Single, options.clone(), &mut |_| true)? {
+        PathResult::Single(outcome) => Ok(outcome),
+        _ => unreachable!("a PathMode::Single search always gives a PathResult::Single"),
+    }
+}
 
-    while !queue.is_empty() {
-        let cur = queue.pop_front().unwrap();
-        let cur_name = cur.0;
-        let cur_depth = cur.1;
-        max_depth = std::cmp::max(max_depth, cur_depth);
-        let cur_id: usize = get_page_id
-            .query_row([cur_name.clone()], |row| row.get(0))
-            .unwrap();
-        let links = find_links
-            .query_map([cur_id], |row| {
-                let pt: String = row.get(0)?;
-                Ok(pt)
+// Json body shared by the path endpoints and the final event of /path/stream, or why no path was found
+fn path_response(
+    start_page: &str,
+    end_page: &str,
+    result: &PathResult,
+) -> Result<serde_json::Value, Unreachable> {
+    match result {
+        PathResult::Single(outcome) => outcome.as_ref().map(|path| {
+            serde_json::json!({
+                "start_page": start_page,
+                "end_page": end_page,
+                "path": path.pages,
+                "edge_types": path.edge_types,
+                "path_length": path.pages.len(),
             })
-            .unwrap();
-        for link in links {
-            // println!("{}", link);
-            let mut link_str = link?;
-            // println!("{}: {}", cur, link_str);
-            if let Err(_e) = check_for_page(&link_str) {
-                if let Ok(redirect) = find_redirect(&link_str) {
-                    link_str = redirect;
-                } else {
-                    continue;
-                }
-            }
-            if !seen.contains_key(&link_str) {
-                seen.insert(link_str.clone(), cur_name.clone());
-                queue.push_back((link_str.clone(), cur_depth + 1));
-            }
-        }
+        }),
+        PathResult::All(outcome) => outcome.as_ref().map(|all_paths| {
+            serde_json::json!({
+                "start_page": start_page,
+                "end_page": end_page,
+                "paths": all_paths.paths.iter().map(|path| &path.pages).collect::<Vec<_>>(),
+                "edge_types": all_paths.paths.iter().map(|path| &path.edge_types).collect::<Vec<_>>(),
+                "path_length": all_paths.paths.first().map_or(0, |path| path.pages.len()),
+                "total_paths": all_paths.total_paths,
+                "offset": all_paths.offset,
+                "limit": all_paths.limit,
+            })
+        }),
+        PathResult::KShortest(outcome) => outcome.as_ref().map(|paths| {
+            serde_json::json!({
+                "start_page": start_page,
+                "end_page": end_page,
+                "paths": paths
+                    .iter()
+                    .map(|path| {
+                        serde_json::json!({
+                            "path": path.pages,
+                            "edge_types": path.edge_types,
+                            "path_length": path.pages.len(),
+                        })
+                    })
+                    .collect::<Vec<serde_json::Value>>(),
+            })
+        }),
     }
-
-    let search_end = search_start.elapsed();
-    println!("Max depth: {}", max_depth);
-    println!("Depth found in {:?}", search_end);
-    Ok(())
+    .map_err(Unreachable::clone)
 }
 
-fn find_shortest_path(start_page: &str, end_page: &str) -> rusqlite::Result<VecDeque<String>> {
-    // seen maps node to parent
-    // parent of start_page is start_page
-    let search_start = Instant::now();
-    let search_conn = Connection::open("main.db").unwrap();
-    let mut num_articles_stmt= search_conn.prepare("select count(id) from pages")?;
-    let num_articles: usize = num_articles_stmt.query_row([], |row| row.get(0))?;
-    println!("Number of articles: {}", num_articles);
-    let mut seen = BitSet::with_capacity(num_articles);
-    let mut parents: HashMap<String, (String, Option<String>)> = HashMap::new();
-
-    let mut get_page_id = search_conn
-        .prepare("select id from PAGES where page_title = (?1)")
-        .unwrap();
-    let mut find_links = search_conn
-        .prepare("select link_title from LINKS where page_id = (?1)")
-        .unwrap();
-
-    parents.insert(start_page.to_string(), (start_page.to_string(), None));
-    let mut queue: VecDeque<String> = VecDeque::from([String::from(start_page)]);
-    let start_page_id: usize = get_page_id.query_row([start_page.to_string().clone()], |row| row.get(0))?;
-    seen.insert(start_page_id);
-
-    let mut found = false;
-    while !queue.is_empty() {
-        found = false;
-        let cur = queue.pop_front().unwrap();
-        let res = get_page_id.query_row([cur.clone()], |row| row.get(0));
-        let cur_id: usize;
-        let mut is_redirect = false;
-        let mut redirect_str = String::new();
-        match res {
-            Ok(id) => cur_id = id,
-            Err(e) => {
-                eprintln!("Error getting page id for {}: {}", cur, e);
-                continue;
+// Responds with the result of a path search, or 404 with the reason if there's no path or a page doesn't exist
+fn path_http_response(
+    start_page: &str,
+    end_page: &str,
+    result: rusqlite::Result<PathResult>,
+) -> actix_web::Result<HttpResponse> {
+    match result {
+        Ok(result) => match path_response(start_page, end_page, &result) {
+            Ok(response) => Ok(HttpResponse::Ok()
+                .append_header(("Access-Control-Allow-Origin", "*"))
+                .json(response)),
+            Err(reason) => {
+                let response = serde_json::json!({
+                    "start_page": start_page,
+                    "end_page": end_page,
+                    "error": reason.error(),
+                    "reason": reason,
+                });
+                Ok(HttpResponse::NotFound()
+                    .append_header(("Access-Control-Allow-Origin", "*"))
+                    .json(response))
             }
+        },
+        Err(e) => {
+            eprintln!("Error finding shortest path: {}", e);
+            Err(actix_web::error::ErrorInternalServerError(
+                "Failed to find shortest path",
+            ))
         }
-        let links = find_links
-            .query_map([cur_id], |row| {
-                let pt: String = row.get(0)?;
-                Ok(pt)
-            })
-            .unwrap();
-        for link in links {
-            let mut link_str = link?;
-            if let Err(_e) = check_for_page(&link_str) {
-                if let Ok(redirect) = find_redirect(&link_str) {
-                    redirect_str = link_str;
-                    is_redirect = true;
-                    link_str = redirect;
-                } else {
-                    continue;
-                }
-            }
-            let link_id: usize = match get_page_id.query_row([link_str.clone()], |row| row.get(0)) {
-                Ok(id) => id,
-                Err(_e) => {
-                    continue;
-                }
-            };
-            if !seen.contains(link_id) {
-                if is_redirect {
-                    parents.insert(link_str.clone(), (cur.clone(), Some(redirect_str.clone())));
-                } else {
-                    parents.insert(link_str.clone(), (cur.clone(), None));
-                }
-                is_redirect = false;
-                seen.insert(link_id);
-                queue.push_back(link_str.clone());
-            }
-            if link_str.as_str() == end_page {
-                println!("Done");
-                found = true;
-                break;
-            }
+    }
+}
+
+/* Reads the options shared by the path endpoints from the query string. avoid and via can be given more than once
+(avoid=A&avoid=B), via pages are visited in the order they're given */
+fn search_options(req: &HttpRequest) -> actix_web::Result<SearchOptions> {
+    let pairs = web::Query::<Vec<(String, String)>>::from_query(req.query_string())?.into_inner();
+    let values = |name: &str| -> Vec<String> {
+        pairs
+            .iter()
+            .filter(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+            .collect()
+    };
+    let number = |name: &str| -> actix_web::Result<Option<u64>> {
+        match values(name).last() {
+            Some(value) => value.parse().map(Some).map_err(|_| {
+                actix_web::error::ErrorBadRequest(format!("{} must be a positive integer", name))
+            }),
+            None => Ok(None),
         }
-        if found {
-            break;
+    };
+    let flag = |name: &str| -> actix_web::Result<bool> {
+        match values(name).last().map(String::as_str) {
+            Some("true") => Ok(true),
+            Some("false") | None => Ok(false),
+            Some(_) => Err(actix_web::error::ErrorBadRequest(format!("{} must be true or false", name))),
         }
+    };
+    let seed = number("seed")?.unwrap_or(0);
+    let tie_break = match values("tie_break").last() {
+        Some(name) => search::TieBreak::parse(name, seed).ok_or_else(|| {
+            actix_web::error::ErrorBadRequest(
+                "tie_break must be one of none, lexicographic, most_linked, least_linked, pagerank or random",
+            )
+        })?,
+        None => search::TieBreak::None,
+    };
+    let options = SearchOptions {
+        avoid: values("avoid"),
+        avoid_disambiguation: flag("avoid_disambiguation")?,
+        via: values("via"),
+        category: query_category(&pairs)?,
+        max_depth: number("max_depth")?.map(|max_depth| max_depth as usize),
+        edge_types: query_edge_types(&pairs)?,
+        tie_break,
+    };
+    options
+        .validate()
+        .map_err(actix_web::error::ErrorBadRequest)?;
+    Ok(options)
+}
+
+/* Category filter given by the category and category_depth query parameters, None if there's no category.
+category_depth defaults to categories::DEFAULT_SUBTREE_DEPTH */
+fn query_category(pairs: &[(String, String)]) -> actix_web::Result<Option<CategoryFilter>> {
+    let value = |name: &str| pairs.iter().rev().find(|(key, _)| key == name).map(|(_, value)| value);
+    let depth = match value("category_depth") {
+        Some(depth) => depth
+            .parse::<usize>()
+            .map_err(|_| actix_web::error::ErrorBadRequest("category_depth must be a positive integer"))?,
+        None => categories::DEFAULT_SUBTREE_DEPTH,
+    };
+    value("category")
+        .map(|category| CategoryFilter::new(category, depth))
+        .transpose()
+        .map_err(actix_web::error::ErrorBadRequest)
+}
+
+/* Edge types allowed by the edge_type and exclude_edge_type query parameters, both can be given more than once.
+Every type is allowed if no edge_type is given */
+fn query_edge_types(pairs: &[(String, String)]) -> actix_web::Result<EdgeTypes> {
+    let edge_type = |name: &String| {
+        EdgeType::parse(name).ok_or_else(|| {
+            actix_web::error::ErrorBadRequest(format!(
+                "Unknown edge type {:?}, expected wikilink or etymology",
+                name
+            ))
+        })
+    };
+    let values = |name: &'static str| pairs.iter().filter(move |(key, _)| key == name).map(|(_, value)| value);
+    let mut edge_types = if values("edge_type").next().is_none() {
+        EdgeTypes::ALL
+    } else {
+        EdgeTypes::NONE
+    };
+    for name in values("edge_type") {
+        edge_types.insert(edge_type(name)?);
     }
-    let mut cur = end_page;
-    let mut path: VecDeque<String> = VecDeque::new();
-    loop {
-        let parent = parents.get(cur).unwrap();
-        if parent.0 != cur {
-            if let Some(redirect_str) = &parent.1 {
-                path.push_front(String::from(format!(
-                    "{} (Redirected from: {})",
-                    cur, redirect_str
-                )));
-            } else {
-                path.push_front(String::from(cur));
-            }
-            cur = parent.0.as_str();
-        } else {
-            path.push_front(String::from(cur));
-            break;
-        }
+    for name in values("exclude_edge_type") {
+        edge_types.remove(edge_type(name)?);
     }
-    let search_end = search_start.elapsed();
-    println!("{:?}", path);
-    println!("Path found in {:?}", search_end);
-    Ok(path)
+    Ok(edge_types)
 }
 
 #[get("/path")] // <- define path parameters
 async fn shortest_path_https(
+    req: HttpRequest,
     params: web::Query<ShortestPathQueryParams>,
 ) -> actix_web::Result<impl Responder> {
+    let options = search_options(&req)?;
     println!(
         "Searching for shortest path between {} and {}",
         &params.startpage, &params.endpage
     );
-    match find_shortest_path(&params.startpage, &params.endpage) {
-        Ok(path) => {
-            let response = serde_json::json!({
-                "start_page": params.startpage,
-                "end_page": params.endpage,
-                "path": path,
-                "path_length": path.len(),
-            });
-            Ok(HttpResponse::Ok()
+    let params = params.into_inner();
+    let (start_page, end_page, mode) = (params.startpage.clone(), params.endpage.clone(), params.mode());
+    let result =
+        web::block(move || cached_shortest_path(&start_page, &end_page, mode, options, &mut |_| true)).await?;
+    path_http_response(&params.startpage, &params.endpage, result)
+}
+
+#[get("/paths/k")]
+async fn k_shortest_paths_https(
+    req: HttpRequest,
+    params: web::Query<KShortestPathsQueryParams>,
+) -> actix_web::Result<impl Responder> {
+    if params.k == 0 || params.k > search::MAX_K {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "k must be between 1 and {}",
+            search::MAX_K
+        )));
+    }
+    let options = search_options(&req)?;
+    if !options.via.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "via isn't supported for k shortest paths",
+        ));
+    }
+    println!(
+        "Searching for {} shortest paths between {} and {}",
+        params.k, &params.startpage, &params.endpage
+    );
+    let mode = PathMode::KShortest { k: params.k };
+    let (start_page, end_page) = (params.startpage.clone(), params.endpage.clone());
+    let result =
+        web::block(move || cached_shortest_path(&start_page, &end_page, mode, options, &mut |_| true)).await?;
+    path_http_response(&params.startpage, &params.endpage, result)
+}
+
+/* Shortest paths for a batch of pairs, searched in parallel. The body is a json array of
+{"startpage": ..., "endpage": ...} objects, json lines of them with a Content-Type of application/x-ndjson, or csv
+with a Content-Type of text/csv. format=csv or format=jsonl changes the response from json. The path search options
+apply to every pair */
+#[post("/paths")]
+async fn batch_paths_https(req: HttpRequest, body: String) -> actix_web::Result<HttpResponse> {
+    let options = search_options(&req)?;
+    let queries = match req.content_type() {
+        "text/csv" => batch::parse_csv(&body),
+        "application/x-ndjson" | "application/jsonl" => batch::parse_jsonl(&body),
+        _ => serde_json::from_str::<Vec<batch::BatchQuery>>(&body).map_err(|e| e.to_string()),
+    }
+    .map_err(actix_web::error::ErrorBadRequest)?;
+    if queries.len() > batch::MAX_BATCH_SIZE {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "At most {} pairs can be searched in one request",
+            batch::MAX_BATCH_SIZE
+        )));
+    }
+    println!("Searching for shortest paths between {} pairs", queries.len());
+    let batch_start = Instant::now();
+    let results = match web::block(move || batch::run_batch(db_path(), queries, &options)).await? {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("Error running batch: {}", e);
+            return Err(actix_web::error::ErrorInternalServerError(
+                "Failed to find shortest paths",
+            ));
+        }
+    };
+    println!("Batch of {} pairs took {:?}", results.len(), batch_start.elapsed());
+
+    let format = web::Query::<Vec<(String, String)>>::from_query(req.query_string())?
+        .into_inner()
+        .into_iter()
+        .find(|(key, _)| key == "format")
+        .map(|(_, value)| value);
+    let mut response = HttpResponse::Ok();
+    response.append_header(("Access-Control-Allow-Origin", "*"));
+    match format.as_deref() {
+        Some("csv") => Ok(response.content_type("text/csv").body(batch::to_csv(&results))),
+        Some("jsonl") => Ok(response
+            .content_type("application/x-ndjson")
+            .body(batch::to_jsonl(&results))),
+        Some("json") | None => Ok(response.json(serde_json::json!({ "results": results }))),
+        Some(_) => Err(actix_web::error::ErrorBadRequest(
+            "format must be one of json, jsonl or csv",
+        )),
+    }
+}
+
+// Formats a single Server-Sent Event, data is sent as one line of json
+fn sse_event(event: &str, data: &serde_json::Value) -> web::Bytes {
+    web::Bytes::from(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+/* Streams the progress of a shortest path search as Server-Sent Events. The search runs on the blocking thread
+pool and sends a "progress" event for every SearchProgress it reports, then finishes with a single "path",
+"no_path" or "error" event. Once the client has gone sending fails and the search is stopped */
+#[get("/path/stream")]
+async fn shortest_path_stream_https(
+    req: HttpRequest,
+    params: web::Query<ShortestPathQueryParams>,
+) -> actix_web::Result<HttpResponse> {
+    let options = search_options(&req)?;
+    println!(
+        "Streaming shortest path search between {} and {}",
+        &params.startpage, &params.endpage
+    );
+    let (tx, rx) = tokio::sync::mpsc::channel::<web::Bytes>(STREAM_BUFFER);
+    let params = params.into_inner();
+    actix_web::rt::task::spawn_blocking(move || {
+        let search_start = Instant::now();
+        let res = cached_shortest_path(
+            &params.startpage,
+            &params.endpage,
+            params.mode(),
+            options,
+            &mut |progress| tx.blocking_send(sse_event("progress", &serde_json::json!(progress))).is_ok(),
+        );
+        if tx.is_closed() {
+            println!(
+                "Client went away, stopped searching between {} and {}",
+                &params.startpage, &params.endpage
+            );
+            return;
+        }
+        let elapsed_ms = search_start.elapsed().as_millis();
+        let event = match res
+            .as_ref()
+            .map(|result| path_response(&params.startpage, &params.endpage, result))
+        {
+            Ok(Ok(mut response)) => {
+                response["elapsed_ms"] = serde_json::json!(elapsed_ms);
+                sse_event("path", &response)
+            }
+            Ok(Err(reason)) => sse_event(
+                "no_path",
+                &serde_json::json!({
+                    "start_page": params.startpage,
+                    "end_page": params.endpage,
+                    "reason": reason,
+                    "elapsed_ms": elapsed_ms,
+                }),
+            ),
+            Err(e) => {
+                eprintln!("Error finding shortest path: {}", e);
+                sse_event(
+                    "error",
+                    &serde_json::json!({ "error": "Failed to find shortest path" }),
+                )
+            }
+        };
+        let _ = tx.blocking_send(event);
+    });
+
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv()
+            .await
+            .map(|event| (Ok::<_, actix_web::Error>(event), rx))
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .append_header(("Cache-Control", "no-cache"))
+        .append_header(("Access-Control-Allow-Origin", "*"))
+        .streaming(stream))
+}
+
+// Pages linking to a page, titles can contain slashes so everything up to /backlinks is the title
+#[get("/page/{title:.+}/backlinks")]
+async fn backlinks_https(
+    title: web::Path<String>,
+    params: web::Query<BacklinksQueryParams>,
+) -> actix_web::Result<impl Responder> {
+    let limit = params
+        .limit
+        .unwrap_or(page::DEFAULT_BACKLINK_LIMIT)
+        .min(page::MAX_BACKLINK_LIMIT);
+    let title = title.into_inner();
+    let page_title = title.clone();
+    let offset = params.offset;
+    match web::block(move || page::backlinks(db_path(), &page_title, offset, limit)).await? {
+        Ok(Some(backlinks)) => Ok(HttpResponse::Ok()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(backlinks)),
+        Ok(None) => Ok(HttpResponse::NotFound()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "title": title, "error": "Page not found" }))),
+        Err(e) => {
+            eprintln!("Error finding backlinks: {}", e);
+            Err(actix_web::error::ErrorInternalServerError(
+                "Failed to find backlinks",
+            ))
+        }
+    }
+}
+
+// Pages near a page by personalized PageRank, titles can contain slashes so everything up to /related is the title
+#[get("/page/{title:.+}/related")]
+async fn related_https(
+    title: web::Path<String>,
+    params: web::Query<RelatedQueryParams>,
+) -> actix_web::Result<impl Responder> {
+    let limit = params
+        .limit
+        .unwrap_or(pagerank::DEFAULT_RELATED_LIMIT)
+        .min(pagerank::MAX_RELATED_LIMIT);
+    let title = title.into_inner();
+    let start_page = title.clone();
+    match web::block(move || pagerank::related(db_path(), &start_page, limit)).await? {
+        Ok(Some(related)) => Ok(HttpResponse::Ok()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(related)),
+        Ok(None) => Ok(HttpResponse::NotFound()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "title": title, "error": "Page not found" }))),
+        Err(e) => {
+            eprintln!("Error finding pages related to {}: {}", title, e);
+            Err(actix_web::error::ErrorInternalServerError(
+                "Failed to find related pages",
+            ))
+        }
+    }
+}
+
+// Links of a page that don't lead to a page, titles can contain slashes so everything up to /red_links is the title
+#[get("/page/{title:.+}/red_links")]
+async fn red_links_https(title: web::Path<String>) -> actix_web::Result<impl Responder> {
+    let title = title.into_inner();
+    let page_title = title.clone();
+    match web::block(move || page::red_links(db_path(), &page_title)).await? {
+        Ok(Some(page)) if page.red_links.is_none() => {
+            Err(actix_web::error::ErrorServiceUnavailable(page::NO_RED_LINKS))
+        }
+        Ok(Some(page)) => Ok(HttpResponse::Ok()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(page)),
+        Ok(None) => Ok(HttpResponse::NotFound()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "title": title, "error": "Page not found" }))),
+        Err(e) => {
+            eprintln!("Error finding red links of {}: {}", title, e);
+            Err(actix_web::error::ErrorInternalServerError(
+                "Failed to find red links",
+            ))
+        }
+    }
+}
+
+// Titles linked to by the most pages without being pages themselves
+#[get("/wanted")]
+async fn wanted_https(params: web::Query<WantedQueryParams>) -> actix_web::Result<impl Responder> {
+    let limit = params
+        .limit
+        .unwrap_or(page::DEFAULT_WANTED_LIMIT)
+        .min(page::MAX_WANTED_LIMIT);
+    let offset = params.offset;
+    match web::block(move || page::most_wanted(db_path(), offset, limit)).await? {
+        Ok(Some(wanted)) => Ok(HttpResponse::Ok()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(wanted)),
+        Ok(None) => Err(actix_web::error::ErrorServiceUnavailable(page::NO_RED_LINKS)),
+        Err(e) => {
+            eprintln!("Error finding most wanted pages: {}", e);
+            Err(actix_web::error::ErrorInternalServerError(
+                "Failed to find most wanted pages",
+            ))
+        }
+    }
+}
+
+// Responds with the answer of a category query, 503 if the database has no categories and 404 with the reason otherwise
+fn category_http_response<T: serde::Serialize>(
+    result: rusqlite::Result<categories::CategoryResult<T>>,
+    failure: &str,
+) -> actix_web::Result<HttpResponse> {
+    match result {
+        Ok(Ok(answer)) => Ok(HttpResponse::Ok()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(answer)),
+        Ok(Err(categories::CategoryError::NoCategories)) => Err(actix_web::error::ErrorServiceUnavailable(
+            categories::CategoryError::NoCategories.to_string(),
+        )),
+        Ok(Err(error)) => {
+            let mut response = serde_json::to_value(&error)?;
+            response["error"] = serde_json::Value::String(error.to_string());
+            Ok(HttpResponse::NotFound()
                 .append_header(("Access-Control-Allow-Origin", "*"))
                 .json(response))
         }
         Err(e) => {
-            eprintln!("Error finding shortest path: {}", e);
+            eprintln!("Error {}: {}", failure, e);
+            Err(actix_web::error::ErrorInternalServerError(format!("Failed {}", failure)))
+        }
+    }
+}
+
+// Categories of an article, titles can contain slashes so everything up to /categories is the title
+#[get("/page/{title:.+}/categories")]
+async fn page_categories_https(title: web::Path<String>) -> actix_web::Result<impl Responder> {
+    let title = title.into_inner();
+    let result = web::block(move || categories::page_categories(db_path(), &title)).await?;
+    category_http_response(result, "finding categories")
+}
+
+/* Subcategories of a category depth levels down and the first limit articles in it. The title can be given with or
+without the Category: prefix */
+#[get("/category/{title:.+}")]
+async fn category_tree_https(
+    title: web::Path<String>,
+    params: web::Query<CategoryQueryParams>,
+) -> actix_web::Result<impl Responder> {
+    let depth = params
+        .depth
+        .unwrap_or(categories::DEFAULT_TREE_DEPTH)
+        .min(categories::MAX_TREE_DEPTH);
+    let limit = params
+        .limit
+        .unwrap_or(categories::DEFAULT_ARTICLE_LIMIT)
+        .min(categories::MAX_ARTICLE_LIMIT);
+    let title = title.into_inner();
+    let result = web::block(move || categories::category_tree(db_path(), &title, depth, limit)).await?;
+    category_http_response(result, "walking the category tree")
+}
+
+// Shortest path between two articles going up the category hierarchy to a shared category and back down
+#[get("/path/categories")]
+async fn category_path_https(params: web::Query<CategoryPathQueryParams>) -> actix_web::Result<impl Responder> {
+    let params = params.into_inner();
+    let result =
+        web::block(move || categories::category_path(db_path(), &params.startpage, &params.endpage)).await?;
+    category_http_response(result, "finding a path through categories")
+}
+
+// Title, description, degree, links and redirects of a page
+#[get("/page/{title:.+}")]
+async fn page_info_https(title: web::Path<String>) -> actix_web::Result<impl Responder> {
+    let title = title.into_inner();
+    let page_title = title.clone();
+    match web::block(move || page::page_info(db_path(), &page_title)).await? {
+        Ok(Some(info)) => Ok(HttpResponse::Ok()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(info)),
+        Ok(None) => Ok(HttpResponse::NotFound()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "title": title, "error": "Page not found" }))),
+        Err(e) => {
+            eprintln!("Error finding page {}: {}", title, e);
             Err(actix_web::error::ErrorInternalServerError(
-                "Failed to find shortest path",
+                "Failed to find page",
             ))
         }
     }
 }
 
-async fn start_server() -> std::io::Result<()> {
-    println!("Starting server at http://0.0.0.0:8080");
-    HttpServer::new(|| App::new().service(shortest_path_https))
-        .bind(("0.0.0.0", 8080))?
-        .run()
-        .await
+// Titles starting with q for autocompletion, from the local database rather than the live Wikipedia search
+#[get("/suggest")]
+async fn suggest_https(params: web::Query<SuggestQueryParams>) -> actix_web::Result<impl Responder> {
+    let limit = params
+        .limit
+        .unwrap_or(page::DEFAULT_SUGGESTION_LIMIT)
+        .min(page::MAX_SUGGESTION_LIMIT);
+    let params = params.into_inner();
+    let query = params.q.clone();
+    match web::block(move || page::suggest(db_path(), &query, limit)).await? {
+        Ok(suggestions) => Ok(HttpResponse::Ok()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "query": params.q, "suggestions": suggestions }))),
+        Err(e) => {
+            eprintln!("Error finding suggestions for {}: {}", params.q, e);
+            Err(actix_web::error::ErrorInternalServerError(
+                "Failed to find suggestions",
+            ))
+        }
+    }
 }
 
-fn start_cli() {
-    println!("Wikimapia v0.2.0. Enter 'h' for list of commands");
-    loop {
-        print!("> ");
-        std::io::stdout().flush().unwrap();
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim();
-
-        match input {
-            "h" => {
-                println!("h          Displays this message");
-                println!("reseed     Re seeds database of connections");
-                println!("search     Starts shortest path search between articles");
-                println!("exit       Exits application")
-            }
-            "reseed" => {
-                if let Err(e) = seed_db() {
-                    eprintln!("Error seeding database: {}", e);
-                }
-            }
-            "search" => {
-                print!("Enter start page: ");
-                std::io::stdout().flush().unwrap();
-                let mut start_page = String::new();
-                std::io::stdin().read_line(&mut start_page).unwrap();
-                let start_page = start_page.trim();
-                if let Err(e) = check_for_page(start_page) {
-                    eprintln!("Page {} doesn't exist: {}", start_page, e);
-                    continue;
-                }
-
-                print!("Enter end page: ");
-                std::io::stdout().flush().unwrap();
-                let mut end_page = String::new();
-                std::io::stdin().read_line(&mut end_page).unwrap();
-                let end_page = end_page.trim();
-                if let Err(e) = check_for_page(end_page) {
-                    eprintln!("Page {} doesn't exist: {}", end_page, e);
-                    continue;
-                }
+// Articles matching the words in q, searched for in titles and descriptions
+#[get("/search")]
+async fn text_search_https(params: web::Query<TextSearchQueryParams>) -> actix_web::Result<impl Responder> {
+    let limit = params
+        .limit
+        .unwrap_or(page::DEFAULT_TEXT_SEARCH_LIMIT)
+        .min(page::MAX_TEXT_SEARCH_LIMIT);
+    let params = params.into_inner();
+    let (query, offset) = (params.q.clone(), params.offset);
+    match web::block(move || page::find(db_path(), &query, offset, limit)).await? {
+        Ok(Some(results)) => Ok(HttpResponse::Ok()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({
+                "query": params.q,
+                "offset": params.offset,
+                "limit": limit,
+                "results": results,
+            }))),
+        Ok(None) => Err(actix_web::error::ErrorServiceUnavailable(page::NO_SEARCH_INDEX)),
+        Err(e) => {
+            eprintln!("Error searching for {}: {}", params.q, e);
+            Err(actix_web::error::ErrorInternalServerError(
+                "Failed to search articles",
+            ))
+        }
+    }
+}
 
-                let _path = find_shortest_path(start_page, end_page);
-            }
-            "depth" => {
-                print!("Enter start page: ");
-                std::io::stdout().flush().unwrap();
-                let mut start_page = String::new();
-                std::io::stdin().read_line(&mut start_page).unwrap();
-                let start_page = start_page.trim();
-                if let Err(e) = check_for_page(start_page) {
-                    eprintln!("Page {} doesn't exist: {}", start_page, e);
-                    continue;
-                }
+/* How far the pages reachable from a page are, or with reverse=true how far the pages that can reach it are. Titles
+can contain slashes. Takes edge_type, exclude_edge_type, category and category_depth like the path endpoints and
+limit, the number of farthest pages listed */
+#[get("/depth/{title:.+}")]
+async fn depth_https(title: web::Path<String>, req: HttpRequest) -> actix_web::Result<impl Responder> {
+    let pairs = web::Query::<Vec<(String, String)>>::from_query(req.query_string())?.into_inner();
+    let edge_types = query_edge_types(&pairs)?;
+    let category = query_category(&pairs)?;
+    let limit = match pairs.iter().rev().find(|(key, _)| key == "limit") {
+        Some((_, limit)) => limit
+            .parse::<usize>()
+            .map_err(|_| actix_web::error::ErrorBadRequest("limit must be a positive integer"))?,
+        None => depth::DEFAULT_FARTHEST_LIMIT,
+    }
+    .min(depth::MAX_FARTHEST_LIMIT);
+    let direction = match pairs.iter().rev().find(|(key, _)| key == "reverse") {
+        Some((_, reverse)) if reverse == "true" => depth::Direction::Reverse,
+        Some((_, reverse)) if reverse != "false" => {
+            return Err(actix_web::error::ErrorBadRequest("reverse must be true or false"));
+        }
+        _ => depth::Direction::Forward,
+    };
+    let title = title.into_inner();
+    let start_page = title.clone();
+    let report = web::block(move || {
+        depth::depth_report(db_path(), &start_page, direction, edge_types, category.as_ref(), limit)
+    })
+    .await?;
+    match report {
+        Ok(Ok(report)) => Ok(HttpResponse::Ok()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(report)),
+        Ok(Err(reason)) => Ok(HttpResponse::NotFound()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "title": title, "error": reason.error(), "reason": reason }))),
+        Err(e) => {
+            eprintln!("Error finding depth from {}: {}", title, e);
+            Err(actix_web::error::ErrorInternalServerError(
+                "Failed to find depth",
+            ))
+        }
+    }
+}
 
-                let _ = find_depth(start_page);
-            }
-            "exit" => break,
-            _ => println!("Invalid input, enter 'h' for list of commands."),
+// Size of the database, its most linked pages and the degree distribution once the degrees command has been run
+#[get("/stats")]
+async fn stats_https(params: web::Query<StatsQueryParams>) -> actix_web::Result<impl Responder> {
+    let top = params.top.unwrap_or(db_stats::DEFAULT_STATS_TOP).min(db_stats::MAX_STATS_TOP);
+    match web::block(move || db_stats::stats(db_path(), top)).await? {
+        Ok(stats) => Ok(HttpResponse::Ok()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(stats)),
+        Err(e) => {
+            eprintln!("Error reading stats: {}", e);
+            Err(actix_web::error::ErrorInternalServerError("Failed to read stats"))
         }
     }
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 1 {
-        let mode = &args[1];
-        if mode == "server" {
-            actix_rt::System::new().block_on(async {
-                start_server().await.expect("Server failed");
-            });
-        } else if mode == "cli" {
-            start_cli();
+// Both degree histograms as csv for plotting, 404 until the degrees command has been run
+#[get("/stats/histogram.csv")]
+async fn degree_histogram_https() -> actix_web::Result<impl Responder> {
+    let stats = web::block(|| {
+        let conn = Connection::open(db_path())?;
+        degrees::degree_stats(&conn, 0)
+    })
+    .await?;
+    match stats {
+        Ok(Some(stats)) => {
+            let mut csv = Vec::new();
+            degrees::write_histogram_csv(&stats, &mut csv)?;
+            Ok(HttpResponse::Ok()
+                .append_header(("Access-Control-Allow-Origin", "*"))
+                .content_type("text/csv")
+                .body(csv))
+        }
+        Ok(None) => Ok(HttpResponse::NotFound()
+            .append_header(("Access-Control-Allow-Origin", "*"))
+            .json(serde_json::json!({ "error": "Degrees haven't been computed, run the degrees command" }))),
+        Err(e) => {
+            eprintln!("Error reading degree histogram: {}", e);
+            Err(actix_web::error::ErrorInternalServerError("Failed to read degree histogram"))
         }
-    } else {
-        println!("Must specify mode (cli or server)");
     }
 }
+
+#[get("/cache")]
+async fn cache_stats_https() -> impl Responder {
+    HttpResponse::Ok()
+        .append_header(("Access-Control-Allow-Origin", "*"))
+        .json(PATH_CACHE.stats())
+}
+
+async fn start_server(host: &str, port: u16) -> std::io::Result<()> {
+    println!("Starting server at http://{}:{}", host, port);
+    HttpServer::new(|| {
+        App::new()
+            // Batches of pairs for POST /paths are bigger than the default limit of 256kB
+            .app_data(web::PayloadConfig::new(16 * 1024 * 1024))
+            .service(shortest_path_https)
+            .service(shortest_path_stream_https)
+            .service(k_shortest_paths_https)
+            .service(batch_paths_https)
+            // The routes under /page/{title} go before page_info_https, its title pattern would also match their paths
+            .service(backlinks_https)
+            .service(related_https)
+            .service(red_links_https)
+            .service(page_categories_https)
+            .service(page_info_https)
+            .service(suggest_https)
+            .service(text_search_https)
+            .service(wanted_https)
+            .service(category_tree_https)
+            .service(category_path_https)
+            .service(depth_https)
+            .service(stats_https)
+            .service(degree_histogram_https)
+            .service(cache_stats_https)
+    })
+        .bind((host, port))?
+        .run()
+        .await
+}
+
+fn main() -> ExitCode {
+    cli::run(cli::Cli::parse())
+}
@@ -0,0 +1,229 @@
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::search::{EdgeType, Graph, RedLink, Suggestion, TextMatch};
+use crate::str_utils;
+
+// Number of backlinks returned when no limit is given, and the most that can be asked for
+pub const DEFAULT_BACKLINK_LIMIT: usize = 100;
+pub const MAX_BACKLINK_LIMIT: usize = 1000;
+
+// A page linking to another, redirect is set if it only links to it through that redirect
+#[derive(Serialize)]
+pub struct Backlink {
+    pub title: String,
+    pub redirect: Option<String>,
+}
+
+/* Pages linking to a page. total counts every linking page, direct the ones with a link to the page itself and
+via_redirect the ones that only link to one of its redirects */
+#[derive(Serialize)]
+pub struct Backlinks {
+    pub title: String,
+    pub page_id: usize,
+    pub total: usize,
+    pub direct: usize,
+    pub via_redirect: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub backlinks: Vec<Backlink>,
+}
+
+/* Backlinks offset..offset + limit of the page title refers to, title can be a redirect. Returns None if there's
+no such page */
+pub fn backlinks(
+    db_path: &str,
+    title: &str,
+    offset: usize,
+    limit: usize,
+) -> rusqlite::Result<Option<Backlinks>> {
+    let graph = Graph::open(db_path)?;
+    let Some((page_id, title)) = graph.resolve(&str_utils::normalize_title(title))? else {
+        return Ok(None);
+    };
+    let (total, direct) = graph.backlink_counts(&title)?;
+    let backlinks = graph
+        .backlinks(&title, offset, limit)?
+        .into_iter()
+        .map(|link| Backlink {
+            title: link.title,
+            redirect: link.redirect,
+        })
+        .collect();
+    Ok(Some(Backlinks {
+        title,
+        page_id,
+        total,
+        direct,
+        via_redirect: total - direct,
+        offset,
+        limit,
+        backlinks,
+    }))
+}
+
+// An outgoing link of a page, redirect is set if the link went through that redirect
+#[derive(Serialize)]
+pub struct OutgoingLink {
+    pub title: String,
+    pub redirect: Option<String>,
+    pub edge_type: EdgeType,
+}
+
+/* What's known about a page. in_degree is the number of pages linking to it, the same total /backlinks gives, and
+out_degree the number of distinct pages it links to. disambiguation is set for disambiguation pages. pagerank is
+None until the pagerank command has been run. redirects are the titles redirecting to it */
+#[derive(Serialize)]
+pub struct PageInfo {
+    pub title: String,
+    pub page_id: usize,
+    pub description: Option<String>,
+    pub disambiguation: bool,
+    pub in_degree: usize,
+    pub out_degree: usize,
+    pub pagerank: Option<f64>,
+    pub links: Vec<OutgoingLink>,
+    pub redirects: Vec<String>,
+}
+
+// Info about the page title refers to, title can be a redirect. Returns None if there's no such page
+pub fn page_info(db_path: &str, title: &str) -> rusqlite::Result<Option<PageInfo>> {
+    let graph = Graph::open(db_path)?;
+    let Some((page_id, title)) = graph.resolve(&str_utils::normalize_title(title))? else {
+        return Ok(None);
+    };
+    let (in_degree, _) = graph.backlink_counts(&title)?;
+    let links = graph.links(page_id)?;
+    // A page linked to directly and through a redirect, or by both edge types, only counts once
+    let out_degree = links.iter().map(|link| link.id).collect::<HashSet<usize>>().len();
+    let links: Vec<OutgoingLink> = links
+        .into_iter()
+        .map(|link| OutgoingLink {
+            title: link.title,
+            redirect: link.redirect,
+            edge_type: link.edge_type,
+        })
+        .collect();
+    Ok(Some(PageInfo {
+        page_id,
+        description: graph.description(page_id)?,
+        disambiguation: graph.is_disambiguation(page_id)?,
+        in_degree,
+        out_degree,
+        pagerank: graph.pagerank(page_id)?,
+        links,
+        redirects: graph.redirects_to(&title)?,
+        title,
+    }))
+}
+
+// Shortest prefix of a title did_you_mean looks for suggestions with, shorter ones match too many titles to help
+const MIN_DID_YOU_MEAN_PREFIX: usize = 3;
+
+// Number of suggestions returned when no limit is given, and the most that can be asked for
+pub const DEFAULT_SUGGESTION_LIMIT: usize = 10;
+pub const MAX_SUGGESTION_LIMIT: usize = 50;
+
+/* Titles completing what's been typed so far, pages and redirects to them. The query is normalized like a title
+so "albert_ein" suggests "Albert Einstein" */
+pub fn suggest(db_path: &str, query: &str, limit: usize) -> rusqlite::Result<Vec<Suggestion>> {
+    let prefix = str_utils::normalize_title(query);
+    if prefix.is_empty() {
+        return Ok(Vec::new());
+    }
+    Graph::open(db_path)?.suggestions(&prefix, limit)
+}
+
+// Number of red links returned by most_wanted when no limit is given, and the most that can be asked for
+pub const DEFAULT_WANTED_LIMIT: usize = 100;
+pub const MAX_WANTED_LIMIT: usize = 1000;
+
+// Why red links can't be listed when the database has no RED_LINKS
+pub const NO_RED_LINKS: &str = "The database was seeded before red links were collected";
+
+// Titles linked to by the most pages without being articles, total counts every such title
+#[derive(Serialize)]
+pub struct MostWanted {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub wanted: Vec<RedLink>,
+}
+
+// Most wanted articles offset..offset + limit, None if the database has no RED_LINKS
+pub fn most_wanted(db_path: &str, offset: usize, limit: usize) -> rusqlite::Result<Option<MostWanted>> {
+    let graph = Graph::open(db_path)?;
+    Ok(graph.most_wanted(offset, limit)?.map(|(total, wanted)| MostWanted {
+        total,
+        offset,
+        limit,
+        wanted,
+    }))
+}
+
+/* Broken outgoing links of a page, with the number of pages linking to each of them. red_links is None if the
+database has no RED_LINKS */
+#[derive(Serialize)]
+pub struct PageRedLinks {
+    pub title: String,
+    pub page_id: usize,
+    pub red_links: Option<Vec<RedLink>>,
+}
+
+// Red links of the page title refers to, title can be a redirect. Returns None if there's no such page
+pub fn red_links(db_path: &str, title: &str) -> rusqlite::Result<Option<PageRedLinks>> {
+    let graph = Graph::open(db_path)?;
+    let Some((page_id, title)) = graph.resolve(&str_utils::normalize_title(title))? else {
+        return Ok(None);
+    };
+    Ok(Some(PageRedLinks {
+        title,
+        page_id,
+        red_links: graph.red_links(page_id)?,
+    }))
+}
+
+// Number of full text search results returned when no limit is given, and the most that can be asked for
+pub const DEFAULT_TEXT_SEARCH_LIMIT: usize = 20;
+pub const MAX_TEXT_SEARCH_LIMIT: usize = 100;
+
+// Why a full text search can't be run when the database has no PAGE_SEARCH
+pub const NO_SEARCH_INDEX: &str = "The full text search index wasn't built when the database was seeded";
+
+// Results offset..offset + limit of a full text search, None if the database has no full text search index
+pub fn find(
+    db_path: &str,
+    query: &str,
+    offset: usize,
+    limit: usize,
+) -> rusqlite::Result<Option<Vec<TextMatch>>> {
+    Graph::open(db_path)?.search_text(query, offset, limit)
+}
+
+/* Pages that might have been meant by a title that isn't one, most likely first. Titles that only differ in case come
+first, then titles starting with it. If nothing starts with it characters are dropped from its end until something
+does, which catches typos near the end of a title */
+pub fn did_you_mean(db_path: &str, title: &str, limit: usize) -> rusqlite::Result<Vec<String>> {
+    let graph = Graph::open(db_path)?;
+    let title = str_utils::normalize_title(title);
+    let mut candidates = graph.titles_ignoring_case(&title, limit)?;
+    let mut prefix = title.as_str();
+    while prefix.chars().count() >= MIN_DID_YOU_MEAN_PREFIX {
+        let suggestions = graph.suggestions(prefix, limit)?;
+        if !suggestions.is_empty() {
+            candidates.extend(
+                suggestions
+                    .into_iter()
+                    .map(|suggestion| suggestion.redirect_to.unwrap_or(suggestion.title)),
+            );
+            break;
+        }
+        let mut chars = prefix.chars();
+        chars.next_back();
+        prefix = chars.as_str();
+    }
+    let mut seen = HashSet::new();
+    candidates.retain(|candidate| seen.insert(candidate.clone()));
+    candidates.truncate(limit);
+    Ok(candidates)
+}
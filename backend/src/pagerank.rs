@@ -0,0 +1,351 @@
+use rusqlite::{Connection, params};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::search::Graph;
+use crate::{db_stats, str_utils};
+
+// Defaults of the pagerank command
+pub const DEFAULT_DAMPING: f64 = 0.85;
+pub const DEFAULT_TOLERANCE: f64 = 1e-6;
+pub const DEFAULT_MAX_ITERATIONS: usize = 100;
+
+// Number of best ranked pages listed once the scores have been stored
+const TOP_PAGES: usize = 10;
+
+/* damping is the chance the random surfer follows a link rather than jumping to a random page. Iteration stops once
+the scores change by less than tolerance in total, or after max_iterations */
+pub struct PagerankOptions {
+    pub damping: f64,
+    pub tolerance: f64,
+    pub max_iterations: usize,
+}
+
+impl Default for PagerankOptions {
+    fn default() -> PagerankOptions {
+        PagerankOptions {
+            damping: DEFAULT_DAMPING,
+            tolerance: DEFAULT_TOLERANCE,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+        }
+    }
+}
+
+impl PagerankOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..1.0).contains(&self.damping) {
+            return Err(String::from("damping must be at least 0 and less than 1"));
+        }
+        if self.tolerance.is_nan() || self.tolerance <= 0.0 {
+            return Err(String::from("tolerance must be greater than 0"));
+        }
+        if self.max_iterations == 0 {
+            return Err(String::from("iterations must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+pub struct RankedPage {
+    pub title: String,
+    pub pagerank: f64,
+}
+
+/* How the computation went. links counts distinct links between different pages, dangling the pages without any.
+delta is the total change of the scores in the last iteration */
+#[derive(Serialize)]
+pub struct PagerankSummary {
+    pub pages: usize,
+    pub links: usize,
+    pub dangling: usize,
+    pub iterations: usize,
+    pub converged: bool,
+    pub delta: f64,
+    pub top: Vec<RankedPage>,
+    pub elapsed_ms: u128,
+}
+
+/* Link graph held in memory for computations over every page, with pages numbered 0..ids.len() in the order of their
+ids. The links of page i are targets[offsets[i]..offsets[i + 1]] */
+pub struct LinkGraph {
+    pub ids: Vec<usize>,
+    pub offsets: Vec<usize>,
+    pub targets: Vec<u32>,
+}
+
+impl LinkGraph {
+    pub fn links(&self, i: usize) -> &[u32] {
+        &self.targets[self.offsets[i]..self.offsets[i + 1]]
+    }
+
+    pub fn is_dangling(&self, i: usize) -> bool {
+        self.offsets[i] == self.offsets[i + 1]
+    }
+
+    // The same pages with every link turned around, the links of page i are then the pages linking to it
+    pub fn reversed(&self) -> LinkGraph {
+        let n = self.ids.len();
+        let mut offsets = vec![0; n + 1];
+        for &target in &self.targets {
+            offsets[target as usize + 1] += 1;
+        }
+        for i in 0..n {
+            offsets[i + 1] += offsets[i];
+        }
+        let mut next = offsets.clone();
+        let mut targets = vec![0; self.targets.len()];
+        for source in 0..n {
+            for &target in self.links(source) {
+                targets[next[target as usize]] = source as u32;
+                next[target as usize] += 1;
+            }
+        }
+        LinkGraph {
+            ids: self.ids.clone(),
+            offsets,
+            targets,
+        }
+    }
+}
+
+/* Loads every link that leads to a page, links through redirects lead to the page the redirect points to. A page
+linking to another several times is counted once and links from a page to itself are left out */
+pub fn load_graph(conn: &Connection) -> rusqlite::Result<LinkGraph> {
+    let mut stmt = conn.prepare("select id from PAGES order by id")?;
+    let ids = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<usize>>>()?;
+    let mut index = vec![u32::MAX; ids.last().map_or(0, |&id| id + 1)];
+    for (i, &id) in ids.iter().enumerate() {
+        index[id] = i as u32;
+    }
+
+    let mut stmt = conn.prepare(
+        "select l.page_id, coalesce(p.id, rp.id)
+        from LINKS l
+        left join PAGES p on p.page_title = l.link_title
+        left join REDIRECTS r on p.id is null and r.page_title = l.link_title
+        left join PAGES rp on rp.page_title = r.redirect_title
+        where coalesce(p.id, rp.id) is not null
+        order by l.page_id",
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut offsets = vec![0];
+    let mut targets: Vec<u32> = Vec::new();
+    let mut links: Vec<u32> = Vec::new();
+    while let Some(row) = rows.next()? {
+        // Links left behind by a section that didn't finish seeding can come from pages that aren't there
+        let Some(&source) = index.get(row.get::<_, usize>(0)?).filter(|&&source| source != u32::MAX) else {
+            continue;
+        };
+        let source = source as usize;
+        let target = index[row.get::<_, usize>(1)?];
+        // Links are ordered by page, so every page before this one has had all its links read
+        while offsets.len() - 1 < source {
+            finish_page(&mut offsets, &mut targets, &mut links);
+        }
+        if target as usize != source {
+            links.push(target);
+        }
+    }
+    while offsets.len() <= ids.len() {
+        finish_page(&mut offsets, &mut targets, &mut links);
+    }
+    Ok(LinkGraph { ids, offsets, targets })
+}
+
+// Adds the links read for the next page without repeats and marks where its links end
+fn finish_page(offsets: &mut Vec<usize>, targets: &mut Vec<u32>, links: &mut Vec<u32>) {
+    links.sort_unstable();
+    links.dedup();
+    targets.append(links);
+    offsets.push(targets.len());
+}
+
+/* Power iteration PageRank. A dangling page has no links to share its score through, so its score is spread over
+every page as if it linked to all of them. Scores add up to 1 */
+fn iterate(graph: &LinkGraph, options: &PagerankOptions) -> (Vec<f64>, usize, bool, f64) {
+    let n = graph.ids.len();
+    let mut rank = vec![1.0 / n as f64; n];
+    let mut next = vec![0.0; n];
+    let mut delta = 0.0;
+    for iteration in 1..=options.max_iterations {
+        let dangling: f64 = (0..n).filter(|&i| graph.is_dangling(i)).map(|i| rank[i]).sum();
+        let base = (1.0 - options.damping + options.damping * dangling) / n as f64;
+        next.fill(base);
+        for (i, score) in rank.iter().enumerate() {
+            let links = graph.links(i);
+            if links.is_empty() {
+                continue;
+            }
+            let share = options.damping * score / links.len() as f64;
+            for &target in links {
+                next[target as usize] += share;
+            }
+        }
+        delta = rank.iter().zip(&next).map(|(old, new)| (old - new).abs()).sum();
+        std::mem::swap(&mut rank, &mut next);
+        if delta < options.tolerance {
+            return (rank, iteration, true, delta);
+        }
+    }
+    (rank, options.max_iterations, false, delta)
+}
+
+/* Computes the PageRank of every page over the link graph and stores it in PAGE_STATS, replacing the scores of an
+earlier run. Reseeding drops PAGE_STATS, so it has to be run again after every seed */
+pub fn compute_pagerank(db_path: &str, options: &PagerankOptions) -> rusqlite::Result<PagerankSummary> {
+    let start = Instant::now();
+    let mut conn = Connection::open(db_path)?;
+    let graph = load_graph(&conn)?;
+    eprintln!(
+        "Loaded {} pages and {} links in {:?}",
+        graph.ids.len(),
+        graph.targets.len(),
+        start.elapsed()
+    );
+    let (rank, iterations, converged, delta) = if graph.ids.is_empty() {
+        (Vec::new(), 0, true, 0.0)
+    } else {
+        iterate(&graph, options)
+    };
+
+    let tx = conn.transaction()?;
+    db_stats::ensure_page_stats_column(&tx, "pagerank", "real")?;
+    tx.execute_batch(
+        "delete from PAGE_STATS where page_id not in (select id from PAGES);
+        update PAGE_STATS set pagerank = null;",
+    )?;
+    {
+        let mut stmt = tx.prepare(
+            "insert into PAGE_STATS(page_id, pagerank) values (?1, ?2)
+            on conflict(page_id) do update set pagerank = excluded.pagerank",
+        )?;
+        for (&id, &score) in graph.ids.iter().zip(&rank) {
+            stmt.execute(params![id, score])?;
+        }
+    }
+    tx.commit()?;
+
+    let mut stmt = conn.prepare(
+        "select p.page_title, s.pagerank from PAGE_STATS s join PAGES p on p.id = s.page_id
+        order by s.pagerank desc limit (?1)",
+    )?;
+    let top = stmt
+        .query_map(params![TOP_PAGES as i64], |row| {
+            Ok(RankedPage {
+                title: row.get(0)?,
+                pagerank: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<RankedPage>>>()?;
+    Ok(PagerankSummary {
+        pages: graph.ids.len(),
+        links: graph.targets.len(),
+        dangling: (0..graph.ids.len()).filter(|&i| graph.is_dangling(i)).count(),
+        iterations,
+        converged,
+        delta,
+        top,
+        elapsed_ms: start.elapsed().as_millis(),
+    })
+}
+
+// Number of related pages returned when no limit is given, and the most that can be asked for
+pub const DEFAULT_RELATED_LIMIT: usize = 10;
+pub const MAX_RELATED_LIMIT: usize = 50;
+
+// Chance the walk jumps back to the page it started from at every step
+const RESTART_PROBABILITY: f64 = 0.15;
+
+/* Residual score below which a page's score isn't pushed to its links anymore. Together with MAX_PUSHES and
+RELATED_TIME_LIMIT this bounds how much of the graph is looked at, only pages near the start get pushed */
+const PUSH_THRESHOLD: f64 = 1e-4;
+const MAX_PUSHES: usize = 5000;
+const RELATED_TIME_LIMIT: Duration = Duration::from_millis(500);
+
+#[derive(Serialize)]
+pub struct RelatedPage {
+    pub title: String,
+    pub score: f64,
+}
+
+/* Pages related to a page, best first. pushes is the number of pages whose links were followed, complete is false if
+the search was cut short by MAX_PUSHES or RELATED_TIME_LIMIT before every residual score was below PUSH_THRESHOLD */
+#[derive(Serialize)]
+pub struct Related {
+    pub title: String,
+    pub page_id: usize,
+    pub related: Vec<RelatedPage>,
+    pub pushes: usize,
+    pub complete: bool,
+    pub elapsed_ms: u128,
+}
+
+/* Personalized PageRank from the page title refers to, approximated by pushing scores along links from the page
+outwards (Andersen, Chung and Lang). It's the chance a random walk that keeps returning to the page ends up on each
+other page. A page without links sends its walk back to the start. title can be a redirect, returns None if there's
+no such page */
+pub fn related(db_path: &str, title: &str, limit: usize) -> rusqlite::Result<Option<Related>> {
+    let start = Instant::now();
+    let graph = Graph::open(db_path)?;
+    let Some((start_id, title)) = graph.resolve(&str_utils::normalize_title(title))? else {
+        return Ok(None);
+    };
+
+    let mut scores: HashMap<usize, f64> = HashMap::new();
+    let mut residuals: HashMap<usize, f64> = HashMap::from([(start_id, 1.0)]);
+    let mut queue = VecDeque::from([start_id]);
+    let mut pushes = 0;
+    let mut complete = true;
+    while let Some(id) = queue.pop_front() {
+        if pushes == MAX_PUSHES || start.elapsed() >= RELATED_TIME_LIMIT {
+            complete = false;
+            break;
+        }
+        let residual = residuals.insert(id, 0.0).unwrap_or(0.0);
+        if residual < PUSH_THRESHOLD {
+            continue;
+        }
+        pushes += 1;
+        *scores.entry(id).or_default() += RESTART_PROBABILITY * residual;
+
+        let mut links: Vec<usize> = graph.links(id)?.into_iter().map(|link| link.id).collect();
+        links.sort_unstable();
+        links.dedup();
+        let targets = if links.is_empty() { vec![start_id] } else { links };
+        let share = (1.0 - RESTART_PROBABILITY) * residual / targets.len() as f64;
+        for target in targets {
+            let target_residual = residuals.entry(target).or_default();
+            let below = *target_residual < PUSH_THRESHOLD;
+            *target_residual += share;
+            // Pages already waiting in the queue get the extra score when their turn comes
+            if below && *target_residual >= PUSH_THRESHOLD {
+                queue.push_back(target);
+            }
+        }
+    }
+
+    let mut ranked: Vec<(usize, f64)> = scores.into_iter().filter(|&(id, _)| id != start_id).collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    let related = ranked
+        .into_iter()
+        .take(limit)
+        .map(|(id, score)| {
+            Ok(RelatedPage {
+                title: graph.page_title(id)?,
+                score,
+            })
+        })
+        .collect::<rusqlite::Result<Vec<RelatedPage>>>()?;
+    Ok(Some(Related {
+        title,
+        page_id: start_id,
+        related,
+        pushes,
+        complete,
+        elapsed_ms: start.elapsed().as_millis(),
+    }))
+}
@@ -0,0 +1,1522 @@
+use bit_set::BitSet;
+use regex::Regex;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::categories;
+use crate::str_utils;
+
+// How often a search reports progress while it's still expanding the same depth
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+// Number of paths returned by an all shortest paths search when no limit is given, and the most that can be asked for
+pub const DEFAULT_PATH_LIMIT: usize = 10;
+pub const MAX_PATH_LIMIT: usize = 1000;
+
+// Snapshot of a running breadth first search, reported through the on_progress callback which returns false to stop it
+#[derive(Serialize)]
+pub struct SearchProgress {
+    pub depth: usize,
+    pub frontier_size: usize,
+    pub nodes_visited: usize,
+    pub elapsed_ms: u128,
+}
+
+// Most paths a k shortest paths search can be asked for, every path after the first costs a search per page in it
+pub const MAX_K: usize = 20;
+
+// What a path search returns
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PathMode {
+    // One shortest path
+    Single,
+    // Every shortest path is counted, paths offset..offset + limit of them are returned
+    All { offset: usize, limit: usize },
+    // The k shortest simple paths, which can be longer than the shortest path
+    KShortest { k: usize },
+}
+
+// How a link was written in an article
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeType {
+    // An ordinary [[...]] link
+    Wikilink,
+    // A link to a language's article made from an {{etymology|<language code>}} template
+    Etymology,
+}
+
+impl EdgeType {
+    pub const ALL: [EdgeType; 2] = [EdgeType::Wikilink, EdgeType::Etymology];
+
+    // Name stored in the edge_type column of LINKS and used in query parameters
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EdgeType::Wikilink => "wikilink",
+            EdgeType::Etymology => "etymology",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<EdgeType> {
+        EdgeType::ALL
+            .into_iter()
+            .find(|edge_type| edge_type.as_str() == name)
+    }
+}
+
+// Set of edge types a search is allowed to follow
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EdgeTypes(u8);
+
+impl EdgeTypes {
+    pub const ALL: EdgeTypes = EdgeTypes((1 << EdgeType::ALL.len()) - 1);
+    pub const NONE: EdgeTypes = EdgeTypes(0);
+
+    pub fn contains(&self, edge_type: EdgeType) -> bool {
+        self.0 & (1 << edge_type as u8) != 0
+    }
+
+    pub fn insert(&mut self, edge_type: EdgeType) {
+        self.0 |= 1 << edge_type as u8;
+    }
+
+    pub fn remove(&mut self, edge_type: EdgeType) {
+        self.0 &= !(1 << edge_type as u8);
+    }
+}
+
+impl Default for EdgeTypes {
+    fn default() -> Self {
+        EdgeTypes::ALL
+    }
+}
+
+// A path found by a search, edge_types[i] is how pages[i] links to pages[i + 1]
+#[derive(Clone, Serialize)]
+pub struct FoundPath {
+    pub pages: Vec<String>,
+    pub edge_types: Vec<EdgeType>,
+}
+
+// Result of an all shortest paths search, total_paths saturates at u64::MAX
+#[derive(Clone, Serialize)]
+pub struct AllShortestPaths {
+    pub total_paths: u64,
+    pub offset: usize,
+    pub limit: usize,
+    pub paths: Vec<FoundPath>,
+}
+
+// How a search chooses between paths of the same length
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TieBreak {
+    // Whichever path is found first, depends on the order of rows in the database
+    #[default]
+    None,
+    // The path whose titles come first alphabetically, compared page by page back from the end
+    Lexicographic,
+    // Prefer pages with the most incoming links
+    MostLinked,
+    // Prefer pages with the fewest incoming links, the most obscure route
+    LeastLinked,
+    // Prefer pages with the highest PageRank, pages without a score rank last
+    Pagerank,
+    // A random path, the same seed always picks the same one
+    Random { seed: u64 },
+}
+
+impl TieBreak {
+    // Parses a policy name as used in query parameters, seed is only used by "random"
+    pub fn parse(name: &str, seed: u64) -> Option<TieBreak> {
+        match name {
+            "none" => Some(TieBreak::None),
+            "lexicographic" => Some(TieBreak::Lexicographic),
+            "most_linked" => Some(TieBreak::MostLinked),
+            "least_linked" => Some(TieBreak::LeastLinked),
+            "pagerank" => Some(TieBreak::Pagerank),
+            "random" => Some(TieBreak::Random { seed }),
+            _ => None,
+        }
+    }
+}
+
+/* Options changing which paths a search can return. avoid holds titles of pages that can't be used as intermediate
+pages, entries written as /pattern/ are regexes matched against titles instead. avoid_disambiguation keeps every
+disambiguation page from being used as an intermediate page. via lists pages the path has to go
+through in order and max_depth is the most links the whole path can follow. Only links of the types in edge_types are
+followed. category limits the intermediate pages to the articles of a category subtree. tie_break picks between paths
+of the same length */
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SearchOptions {
+    pub avoid: Vec<String>,
+    pub avoid_disambiguation: bool,
+    pub via: Vec<String>,
+    pub category: Option<CategoryFilter>,
+    pub max_depth: Option<usize>,
+    pub edge_types: EdgeTypes,
+    pub tie_break: TieBreak,
+}
+
+impl SearchOptions {
+    // Normalizes the titles in avoid and via, so that equivalent constraints compare equal
+    pub fn normalized(self) -> SearchOptions {
+        SearchOptions {
+            avoid: self
+                .avoid
+                .into_iter()
+                .map(|entry| match avoid_pattern(&entry) {
+                    Some(_) => entry,
+                    None => str_utils::normalize_title(&entry),
+                })
+                .collect(),
+            avoid_disambiguation: self.avoid_disambiguation,
+            via: self
+                .via
+                .iter()
+                .map(|title| str_utils::normalize_title(title))
+                .collect(),
+            category: self.category,
+            max_depth: self.max_depth,
+            edge_types: self.edge_types,
+            tie_break: self.tie_break,
+        }
+    }
+
+    // Checks that every regex in avoid compiles
+    pub fn validate(&self) -> Result<(), String> {
+        for pattern in self.avoid.iter().filter_map(|entry| avoid_pattern(entry)) {
+            if let Err(e) = Regex::new(pattern) {
+                return Err(format!("Invalid avoid pattern /{}/: {}", pattern, e));
+            }
+        }
+        Ok(())
+    }
+}
+
+/* Articles in a category or in its subcategories down to depth levels, the only pages a search filtered by it can go
+through. The pages it starts and ends at and via pages don't have to be in it */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CategoryFilter {
+    pub category: String,
+    pub depth: usize,
+}
+
+impl CategoryFilter {
+    // The category can be given with or without the Category: prefix
+    pub fn new(category: &str, depth: usize) -> Result<CategoryFilter, String> {
+        if depth > categories::MAX_TREE_DEPTH {
+            return Err(format!("category depth must be at most {}", categories::MAX_TREE_DEPTH));
+        }
+        Ok(CategoryFilter {
+            category: categories::category_title(category),
+            depth,
+        })
+    }
+}
+
+// Returns the regex of an avoid entry written as /pattern/, None if the entry is a title
+fn avoid_pattern(entry: &str) -> Option<&str> {
+    if entry.len() >= 2 && entry.starts_with('/') && entry.ends_with('/') {
+        Some(&entry[1..entry.len() - 1])
+    } else {
+        None
+    }
+}
+
+/* Why a search found no path, names the constraint that made the end page unreachable, or the start, end or via title
+that isn't a page */
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "constraint", rename_all = "snake_case")]
+pub enum Unreachable {
+    // A title the search needs is neither a page nor a redirect to one
+    PageNotFound { title: String },
+    // The category of the category filter has no page, no articles and no subcategories
+    CategoryNotFound { category: String },
+    // There's no path between the pages even without constraints
+    None,
+    // Every path goes through an avoided page
+    Avoid,
+    // There are paths, but all of them follow more than max_depth links
+    MaxDepth { max_depth: usize },
+    // A waypoint, or the end page, can't be reached from the waypoint before it
+    Via { from: String, to: String },
+    // There are paths, but all of them follow a link of an excluded edge type
+    EdgeType,
+    // Every path goes through a page outside the category filter, pages counts the articles inside it
+    Category { category: String, pages: usize },
+}
+
+impl Unreachable {
+    // Short summary responses give next to the reason
+    pub fn error(&self) -> &'static str {
+        match self {
+            Unreachable::PageNotFound { .. } => "Page not found",
+            Unreachable::CategoryNotFound { .. } => "Category not found",
+            _ => "No path found",
+        }
+    }
+}
+
+impl std::fmt::Display for Unreachable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unreachable::PageNotFound { title } => write!(f, "page {} doesn't exist", title),
+            Unreachable::CategoryNotFound { category } => write!(f, "category {} doesn't exist", category),
+            Unreachable::None => write!(f, "there's no path between the pages"),
+            Unreachable::Avoid => write!(f, "every path goes through an avoided page"),
+            Unreachable::MaxDepth { max_depth } => write!(f, "every path follows more than {} links", max_depth),
+            Unreachable::Via { from, to } => write!(f, "{} can't be reached from {}", to, from),
+            Unreachable::EdgeType => write!(f, "every path follows a link of an excluded edge type"),
+            Unreachable::Category { category, pages } => write!(
+                f,
+                "every path goes through a page outside Category:{} ({} articles)",
+                category, pages
+            ),
+        }
+    }
+}
+
+// Result of a search, either what was found or why nothing was
+pub type Outcome<T> = Result<T, Unreachable>;
+
+/* A link from one page to another. If the link went through a redirect, redirect is the title that was linked to.
+disambiguation is set if the page id is a disambiguation page */
+pub struct Link {
+    pub id: usize,
+    pub title: String,
+    pub redirect: Option<String>,
+    pub edge_type: EdgeType,
+    pub disambiguation: bool,
+}
+
+/* A title starting with what was typed, redirect_to is set if the title is a redirect. pagerank is the score of the
+page it leads to, None if the pagerank command hasn't been run */
+#[derive(Serialize)]
+pub struct Suggestion {
+    pub title: String,
+    pub redirect_to: Option<String>,
+    pub in_degree: usize,
+    pub pagerank: Option<f64>,
+}
+
+/* An article matching a full text search. snippet is the part of the title or description that matched best with
+the matching words in <b></b>, a lower score is a better match */
+#[derive(Serialize)]
+pub struct TextMatch {
+    pub title: String,
+    pub description: Option<String>,
+    pub snippet: String,
+    pub score: f64,
+}
+
+// A title linked to that isn't a page, pages counts the pages linking to it
+#[derive(Serialize)]
+pub struct RedLink {
+    pub title: String,
+    pub pages: usize,
+}
+
+// Edge of the breadth first search tree, id is the page the edge comes from
+struct Parent {
+    id: usize,
+    redirect: Option<String>,
+    edge_type: EdgeType,
+}
+
+/* Page in a path, redirect is set if the link to this page went through a redirect and edge_type is the type of
+that link. The first page of a path has no link to it, its edge_type isn't used */
+#[derive(Clone)]
+struct Step {
+    id: usize,
+    redirect: Option<String>,
+    edge_type: EdgeType,
+}
+
+/* Read only view of the link graph stored in the database. Databases seeded before edge types and descriptions
+were recorded don't have LINKS.edge_type or PAGES.description, every link in them is treated as a wikilink and no
+page has a description. Without SUGGESTIONS titles are suggested straight from PAGES, PAGE_SEARCH is only there
+if the full text search index was built. Databases seeded before red links were collected don't have RED_LINKS and
+ones seeded before disambiguation pages were flagged don't have PAGES.disambiguation, no page in them is one */
+pub struct Graph {
+    conn: Connection,
+    has_edge_types: bool,
+    has_descriptions: bool,
+    has_suggestions: bool,
+    has_search_index: bool,
+    has_pagerank: bool,
+    has_components: bool,
+    has_red_links: bool,
+    has_disambiguation: bool,
+}
+
+impl Graph {
+    pub fn open(db_path: &str) -> rusqlite::Result<Graph> {
+        let conn = Connection::open(db_path)?;
+        let has_edge_types = has_column(&conn, "LINKS", "edge_type")?;
+        let has_descriptions = has_column(&conn, "PAGES", "description")?;
+        let has_suggestions = has_table(&conn, "SUGGESTIONS")?;
+        let has_search_index = has_table(&conn, "PAGE_SEARCH")?;
+        let has_page_stats = has_table(&conn, "PAGE_STATS")?;
+        let has_pagerank = has_page_stats && has_column(&conn, "PAGE_STATS", "pagerank")?;
+        let has_components = has_page_stats && has_column(&conn, "PAGE_STATS", "component")?;
+        let has_red_links = has_table(&conn, "RED_LINKS")?;
+        let has_disambiguation = has_column(&conn, "PAGES", "disambiguation")?;
+        Ok(Graph {
+            conn,
+            has_edge_types,
+            has_descriptions,
+            has_suggestions,
+            has_search_index,
+            has_pagerank,
+            has_components,
+            has_red_links,
+            has_disambiguation,
+        })
+    }
+
+    // Largest page id, used to size the BitSets keeping track of visited pages
+    pub fn max_page_id(&self) -> rusqlite::Result<usize> {
+        self.conn
+            .query_row("select coalesce(max(id), 0) from PAGES", [], |row| row.get(0))
+    }
+
+    pub fn page_title(&self, id: usize) -> rusqlite::Result<String> {
+        self.conn
+            .prepare_cached("select page_title from PAGES where id = (?1)")?
+            .query_row(params![id], |row| row.get(0))
+    }
+
+    /* Number of pages linking to a page, directly or through redirects that point to it. A page linking to it more
+    than once, through a redirect or with both edge types, counts once, the same as backlink_counts */
+    pub fn in_degree(&self, id: usize) -> rusqlite::Result<usize> {
+        let (pages, _) = self.backlink_counts(&self.page_title(id)?)?;
+        Ok(pages)
+    }
+
+    // Ids of the articles a category filter lets a search go through, None if the category doesn't exist
+    pub fn category_pages(&self, filter: &CategoryFilter) -> rusqlite::Result<Option<HashSet<usize>>> {
+        categories::subtree_pages(&self.conn, &filter.category, filter.depth)
+    }
+
+    pub fn is_disambiguation(&self, id: usize) -> rusqlite::Result<bool> {
+        if !self.has_disambiguation {
+            return Ok(false);
+        }
+        self.conn
+            .prepare_cached("select disambiguation from PAGES where id = (?1)")?
+            .query_row(params![id], |row| row.get(0))
+    }
+
+    // PageRank of a page, None if the pagerank command hasn't been run
+    pub fn pagerank(&self, id: usize) -> rusqlite::Result<Option<f64>> {
+        if !self.has_pagerank {
+            return Ok(None);
+        }
+        Ok(self
+            .conn
+            .prepare_cached("select pagerank from PAGE_STATS where page_id = (?1)")?
+            .query_row(params![id], |row| row.get(0))
+            .optional()?
+            .flatten())
+    }
+
+    /* Strongly connected component of a page, None if the components command hasn't been run. Links only lead from a
+    component to components with lower numbers, so a page can't reach a page in a component numbered higher than its
+    own */
+    pub fn component(&self, id: usize) -> rusqlite::Result<Option<usize>> {
+        if !self.has_components {
+            return Ok(None);
+        }
+        Ok(self
+            .conn
+            .prepare_cached("select component from PAGE_STATS where page_id = (?1)")?
+            .query_row(params![id], |row| row.get(0))
+            .optional()?
+            .flatten())
+    }
+
+    // Whether the components rule out any path from from_id to to_id, false if they haven't been computed
+    pub fn unreachable_by_components(&self, from_id: usize, to_id: usize) -> rusqlite::Result<bool> {
+        Ok(match (self.component(from_id)?, self.component(to_id)?) {
+            (Some(from), Some(to)) => from < to,
+            _ => false,
+        })
+    }
+
+    /* Outgoing links of a page resolved to page ids. A link title that isn't a page is looked up in REDIRECTS
+    and replaced by the page it redirects to, links that match neither are red links and dropped */
+    pub fn links(&self, id: usize) -> rusqlite::Result<Vec<Link>> {
+        let edge_type = if self.has_edge_types {
+            "l.edge_type"
+        } else {
+            "'wikilink'"
+        };
+        let disambiguation = if self.has_disambiguation {
+            "coalesce(p.disambiguation, rp.disambiguation)"
+        } else {
+            "0"
+        };
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "select coalesce(p.id, rp.id), coalesce(p.page_title, rp.page_title),
+                case when p.id is null then l.link_title end, {}, {}
+            from LINKS l
+            left join PAGES p on p.page_title = l.link_title
+            left join REDIRECTS r on p.id is null and r.page_title = l.link_title
+            left join PAGES rp on rp.page_title = r.redirect_title
+            where l.page_id = (?1) and coalesce(p.id, rp.id) is not null",
+            edge_type, disambiguation
+        ))?;
+        let links = stmt.query_map(params![id], |row| {
+            let edge_type: String = row.get(3)?;
+            Ok(Link {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                redirect: row.get(2)?,
+                edge_type: EdgeType::parse(&edge_type).unwrap_or(EdgeType::Wikilink),
+                disambiguation: row.get(4)?,
+            })
+        })?;
+        links.collect()
+    }
+
+    pub fn description(&self, id: usize) -> rusqlite::Result<Option<String>> {
+        if !self.has_descriptions {
+            return Ok(None);
+        }
+        self.conn
+            .prepare_cached("select description from PAGES where id = (?1)")?
+            .query_row(params![id], |row| row.get(0))
+    }
+
+    // Titles that redirect to title, ordered by title
+    pub fn redirects_to(&self, title: &str) -> rusqlite::Result<Vec<String>> {
+        let mut stmt = self.conn.prepare_cached(
+            "select page_title from REDIRECTS where redirect_title = (?1) order by page_title",
+        )?;
+        let redirects = stmt.query_map(params![title], |row| row.get(0))?;
+        redirects.collect()
+    }
+
+    /* Up to limit titles starting with prefix, ignoring case, highest PageRank first once it's been computed and most
+    linked to first otherwise. Without SUGGESTIONS only page titles starting with prefix exactly are found, ranked the
+    same way with in_degree counted for every one of them, which is slow for short prefixes on a full dump */
+    pub fn suggestions(&self, prefix: &str, limit: usize) -> rusqlite::Result<Vec<Suggestion>> {
+        // Every title starting with prefix sorts between prefix and prefix followed by the largest character
+        let upper_bound = format!("{}{}", prefix, char::MAX);
+        if !self.has_suggestions {
+            let query = if self.has_pagerank {
+                format!(
+                    "select p.page_title, {} as in_degree, ps.pagerank
+                    from PAGES p left join PAGE_STATS ps on ps.page_id = p.id
+                    where p.page_title >= (?1) and p.page_title < (?2)
+                    order by ps.pagerank desc, in_degree desc, p.page_title limit (?3)",
+                    PAGE_IN_DEGREE
+                )
+            } else {
+                format!(
+                    "select p.page_title, {} as in_degree, null from PAGES p
+                    where p.page_title >= (?1) and p.page_title < (?2)
+                    order by in_degree desc, p.page_title limit (?3)",
+                    PAGE_IN_DEGREE
+                )
+            };
+            let mut stmt = self.conn.prepare_cached(&query)?;
+            let suggestions = stmt.query_map(params![prefix, upper_bound, limit as i64], |row| {
+                Ok(Suggestion {
+                    title: row.get(0)?,
+                    redirect_to: None,
+                    in_degree: row.get(1)?,
+                    pagerank: row.get(2)?,
+                })
+            })?;
+            return suggestions.collect();
+        }
+        // With PageRank scores the best ranked pages come first, in_degree only decides between equal scores
+        let query = if self.has_pagerank {
+            "select s.title, s.redirect_to, s.in_degree, ps.pagerank
+            from SUGGESTIONS s
+            left join PAGES p on p.page_title = coalesce(s.redirect_to, s.title)
+            left join PAGE_STATS ps on ps.page_id = p.id
+            where s.title >= (?1) and s.title < (?2)
+            order by ps.pagerank desc, s.in_degree desc, s.title limit (?3)"
+        } else {
+            "select title, redirect_to, in_degree, null from SUGGESTIONS where title >= (?1) and title < (?2)
+            order by in_degree desc, title limit (?3)"
+        };
+        let mut stmt = self.conn.prepare_cached(query)?;
+        let suggestions = stmt.query_map(params![prefix, upper_bound, limit as i64], |row| {
+            Ok(Suggestion {
+                title: row.get(0)?,
+                redirect_to: row.get(1)?,
+                in_degree: row.get(2)?,
+                pagerank: row.get(3)?,
+            })
+        })?;
+        suggestions.collect()
+    }
+
+    /* Articles whose title or description contain every word of query, best BM25 match first with words in the
+    title weighted over words in the description. If no article has every word, articles with any of them are
+    returned instead. Returns None without PAGE_SEARCH */
+    pub fn search_text(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> rusqlite::Result<Option<Vec<TextMatch>>> {
+        if !self.has_search_index {
+            return Ok(None);
+        }
+        // Every word is quoted so punctuation in the query can't be read as FTS5 syntax
+        let words: Vec<String> = query
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| format!("\"{}\"", word))
+            .collect();
+        if words.is_empty() {
+            return Ok(Some(Vec::new()));
+        }
+        let mut terms = words.join(" AND ");
+        let any_match = self
+            .conn
+            .prepare_cached("select 1 from PAGE_SEARCH where PAGE_SEARCH match (?1) limit 1")?
+            .exists(params![terms])?;
+        if !any_match {
+            terms = words.join(" OR ");
+        }
+        let mut stmt = self.conn.prepare_cached(
+            "select p.page_title, p.description, snippet(PAGE_SEARCH, -1, '<b>', '</b>', '...', 16),
+                bm25(PAGE_SEARCH, 10.0, 1.0) as score
+            from PAGE_SEARCH join PAGES p on p.id = PAGE_SEARCH.rowid
+            where PAGE_SEARCH match (?1)
+            order by score
+            limit (?2) offset (?3)",
+        )?;
+        let matches = stmt.query_map(params![terms, limit as i64, offset as i64], |row| {
+            Ok(TextMatch {
+                title: row.get(0)?,
+                description: row.get(1)?,
+                snippet: row.get(2)?,
+                score: row.get(3)?,
+            })
+        })?;
+        matches.collect::<rusqlite::Result<Vec<TextMatch>>>().map(Some)
+    }
+
+    /* Red links offset..offset + limit, the most linked to first, and the number of them. Returns None without
+    RED_LINKS */
+    pub fn most_wanted(&self, offset: usize, limit: usize) -> rusqlite::Result<Option<(usize, Vec<RedLink>)>> {
+        if !self.has_red_links {
+            return Ok(None);
+        }
+        let total = self
+            .conn
+            .query_row("select count(*) from RED_LINKS", [], |row| row.get(0))?;
+        let mut stmt = self.conn.prepare_cached(
+            "select title, pages from RED_LINKS order by pages desc, title limit (?1) offset (?2)",
+        )?;
+        let red_links = stmt.query_map(params![limit as i64, offset as i64], |row| {
+            Ok(RedLink {
+                title: row.get(0)?,
+                pages: row.get(1)?,
+            })
+        })?;
+        Ok(Some((total, red_links.collect::<rusqlite::Result<Vec<RedLink>>>()?)))
+    }
+
+    // Links of a page that don't lead to a page, the most linked to first. Returns None without RED_LINKS
+    pub fn red_links(&self, id: usize) -> rusqlite::Result<Option<Vec<RedLink>>> {
+        if !self.has_red_links {
+            return Ok(None);
+        }
+        let mut stmt = self.conn.prepare_cached(
+            "select distinct rl.title, rl.pages from LINKS l join RED_LINKS rl on rl.title = l.link_title
+            where l.page_id = (?1) order by rl.pages desc, rl.title",
+        )?;
+        let red_links = stmt.query_map(params![id], |row| {
+            Ok(RedLink {
+                title: row.get(0)?,
+                pages: row.get(1)?,
+            })
+        })?;
+        red_links.collect::<rusqlite::Result<Vec<RedLink>>>().map(Some)
+    }
+
+    /* Pages whose title, or the title of a redirect to them, is title ignoring case. Without SUGGESTIONS only page
+    titles are compared, which means reading all of PAGES */
+    pub fn titles_ignoring_case(&self, title: &str, limit: usize) -> rusqlite::Result<Vec<String>> {
+        let query = if self.has_suggestions {
+            "select coalesce(redirect_to, title) from SUGGESTIONS where title = (?1)
+            order by in_degree desc limit (?2)"
+        } else {
+            "select page_title from PAGES where page_title = (?1) collate nocase limit (?2)"
+        };
+        let mut stmt = self.conn.prepare_cached(query)?;
+        let titles = stmt.query_map(params![title, limit as i64], |row| row.get(0))?;
+        titles.collect()
+    }
+
+    // Id and title of the page a title refers to, following a redirect if the title isn't a page
+    pub fn resolve(&self, title: &str) -> rusqlite::Result<Option<(usize, String)>> {
+        self.conn
+            .prepare_cached(
+                "select id, page_title from PAGES where page_title = (?1)
+                union all
+                select p.id, p.page_title from REDIRECTS r join PAGES p on p.page_title = r.redirect_title
+                    where r.page_title = (?1)
+                limit 1",
+            )?
+            .query_row(params![title], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()
+    }
+
+    /* Incoming links of a page, the reverse of links. id and title are those of the page the link comes from and
+    redirect is set if the link went to a redirect pointing at the page. A page linking more than once shows up once
+    for every link */
+    pub fn incoming_links(&self, id: usize) -> rusqlite::Result<Vec<Link>> {
+        let edge_type = if self.has_edge_types {
+            "l.edge_type"
+        } else {
+            "'wikilink'"
+        };
+        let disambiguation = if self.has_disambiguation {
+            "src.disambiguation"
+        } else {
+            "0"
+        };
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "select src.id, src.page_title, s.redirect, s.edge_type, {1}
+            from (
+                select l.page_id, null as redirect, {0} as edge_type
+                    from PAGES p join LINKS l on l.link_title = p.page_title where p.id = (?1)
+                union all
+                select l.page_id, r.page_title, {0}
+                    from PAGES p
+                    join REDIRECTS r on r.redirect_title = p.page_title
+                    join LINKS l on l.link_title = r.page_title
+                    where p.id = (?1)
+            ) s join PAGES src on src.id = s.page_id",
+            edge_type, disambiguation
+        ))?;
+        let links = stmt.query_map(params![id], |row| {
+            let edge_type: String = row.get(3)?;
+            Ok(Link {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                redirect: row.get(2)?,
+                edge_type: EdgeType::parse(&edge_type).unwrap_or(EdgeType::Wikilink),
+                disambiguation: row.get(4)?,
+            })
+        })?;
+        links.collect()
+    }
+
+    /* Pages linking to title ordered by title, a page is listed once however many times it links to title. Links
+    to redirects pointing at title count as links to it, redirect is the title that was linked to when the page
+    only links through a redirect */
+    pub fn backlinks(&self, title: &str, offset: usize, limit: usize) -> rusqlite::Result<Vec<Link>> {
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "{}
+            select p.id, p.page_title, case when count(s.redirect) = count(*) then min(s.redirect) end
+            from sources s join PAGES p on p.id = s.page_id
+            group by s.page_id
+            order by p.page_title
+            limit (?2) offset (?3)",
+            BACKLINK_SOURCES
+        ))?;
+        let backlinks = stmt.query_map(params![title, limit as i64, offset as i64], |row| {
+            Ok(Link {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                redirect: row.get(2)?,
+                edge_type: EdgeType::Wikilink,
+                disambiguation: false,
+            })
+        })?;
+        backlinks.collect()
+    }
+
+    // Number of pages linking to title and how many of them link to it directly rather than only through redirects
+    pub fn backlink_counts(&self, title: &str) -> rusqlite::Result<(usize, usize)> {
+        self.conn
+            .prepare_cached(&format!(
+                "{}
+                select count(*), coalesce(sum(direct), 0)
+                from (select max(s.redirect is null) as direct from sources s group by s.page_id)",
+                BACKLINK_SOURCES
+            ))?
+            .query_row(params![title], |row| Ok((row.get(0)?, row.get(1)?)))
+    }
+}
+
+pub fn has_table(conn: &Connection, table: &str) -> rusqlite::Result<bool> {
+    conn.prepare("select 1 from sqlite_master where type = 'table' and name = (?1)")?
+        .exists(params![table])
+}
+
+pub fn has_column(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    conn.prepare("select 1 from pragma_table_info(?1) where name = (?2)")?
+        .exists(params![table, column])
+}
+
+// Every link to the title ?1, directly or through a redirect to it, and the redirect it went through
+const BACKLINK_SOURCES: &str = "with sources(page_id, redirect) as (
+    select page_id, null from LINKS where link_title = (?1)
+    union all
+    select l.page_id, r.page_title from REDIRECTS r join LINKS l on l.link_title = r.page_title
+        where r.redirect_title = (?1)
+)";
+
+/* Number of distinct pages linking to the page p, directly or through a redirect to it. The same count as
+Graph::in_degree and the in_degree create_suggestions.sql stores */
+const PAGE_IN_DEGREE: &str = "(select count(*) from (
+    select page_id from LINKS where link_title = p.page_title
+    union
+    select l.page_id from REDIRECTS r join LINKS l on l.link_title = r.page_title where r.redirect_title = p.page_title
+))";
+
+// Formats a page in a path, noting the redirect that was followed to get to it
+pub fn path_entry(title: String, redirect: Option<&String>) -> String {
+    match redirect {
+        Some(redirect_str) => format!("{} (Redirected from: {})", title, redirect_str),
+        None => title,
+    }
+}
+
+// Turns a path into titles for display
+fn format_path(graph: &Graph, steps: &[Step]) -> rusqlite::Result<FoundPath> {
+    Ok(FoundPath {
+        pages: steps
+            .iter()
+            .map(|step| Ok(path_entry(graph.page_title(step.id)?, step.redirect.as_ref())))
+            .collect::<rusqlite::Result<Vec<String>>>()?,
+        edge_types: steps.iter().skip(1).map(|step| step.edge_type).collect(),
+    })
+}
+
+// First step of a path
+fn start_step(id: usize) -> Step {
+    Step {
+        id,
+        redirect: None,
+        edge_type: EdgeType::Wikilink,
+    }
+}
+
+// Path from the start of a search to end_id, following the first parent of every page
+fn first_parent_path(parents: &HashMap<usize, Vec<Parent>>, end_id: usize) -> Vec<Step> {
+    let mut steps: VecDeque<Step> = VecDeque::new();
+    let mut cur = end_id;
+    while let Some(parent) = parents[&cur].first() {
+        steps.push_front(Step {
+            id: cur,
+            redirect: parent.redirect.clone(),
+            edge_type: parent.edge_type,
+        });
+        cur = parent.id;
+    }
+    steps.push_front(start_step(cur));
+    steps.into()
+}
+
+// Error a search returns when its on_progress callback asks it to stop, it's never cached
+fn search_cancelled() -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_INTERRUPT),
+        Some(String::from("Search cancelled")),
+    )
+}
+
+/* Breadth first search from start_id that stops once the depth containing end_id has been fully discovered.
+Maps every discovered page to its parents, with all_parents every page one level closer to start_id that links
+to it is kept, otherwise only the first one found. A link is only followed if allowed returns true for the page
+it comes from and the link, and no page further than max_depth links away is discovered.
+Returns None if end_id can't be reached, and search_cancelled() as soon as on_progress returns false */
+fn bfs(
+    graph: &Graph,
+    start_id: usize,
+    end_id: usize,
+    all_parents: bool,
+    allowed: &dyn Fn(usize, &Link) -> bool,
+    max_depth: Option<usize>,
+    on_progress: &mut dyn FnMut(&SearchProgress) -> bool,
+) -> rusqlite::Result<Option<HashMap<usize, Vec<Parent>>>> {
+    let search_start = Instant::now();
+    let mut seen = BitSet::with_capacity(graph.max_page_id()? + 1);
+    let mut parents: HashMap<usize, Vec<Parent>> = HashMap::new();
+    seen.insert(start_id);
+    parents.insert(start_id, Vec::new());
+    if start_id == end_id {
+        return Ok(Some(parents));
+    }
+
+    let capacity = seen.capacity();
+    let mut frontier: Vec<usize> = vec![start_id];
+    let mut depth = 0;
+    while !frontier.is_empty() && max_depth.is_none_or(|max_depth| depth < max_depth) {
+        if !on_progress(&SearchProgress {
+            depth,
+            frontier_size: frontier.len(),
+            nodes_visited: parents.len(),
+            elapsed_ms: search_start.elapsed().as_millis(),
+        }) {
+            return Err(search_cancelled());
+        }
+        let mut last_progress = Instant::now();
+
+        let mut next: Vec<usize> = Vec::new();
+        let mut discovered = BitSet::with_capacity(if all_parents { capacity } else { 0 });
+        for (i, &cur_id) in frontier.iter().enumerate() {
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                last_progress = Instant::now();
+                if !on_progress(&SearchProgress {
+                    depth,
+                    frontier_size: frontier.len() - i + next.len(),
+                    nodes_visited: parents.len(),
+                    elapsed_ms: search_start.elapsed().as_millis(),
+                }) {
+                    return Err(search_cancelled());
+                }
+            }
+            for link in graph.links(cur_id)? {
+                if !allowed(cur_id, &link) {
+                    continue;
+                }
+                if seen.insert(link.id) {
+                    parents.insert(
+                        link.id,
+                        vec![Parent {
+                            id: cur_id,
+                            redirect: link.redirect,
+                            edge_type: link.edge_type,
+                        }],
+                    );
+                    next.push(link.id);
+                    if all_parents {
+                        discovered.insert(link.id);
+                    } else if link.id == end_id {
+                        return Ok(Some(parents));
+                    }
+                } else if discovered.contains(link.id) {
+                    // Already discovered at this depth through another page, another shortest way to get there
+                    let link_parents = parents.get_mut(&link.id).unwrap();
+                    if !link_parents.iter().any(|parent| parent.id == cur_id) {
+                        link_parents.push(Parent {
+                            id: cur_id,
+                            redirect: link.redirect,
+                            edge_type: link.edge_type,
+                        });
+                    }
+                }
+            }
+        }
+        if parents.contains_key(&end_id) {
+            return Ok(Some(parents));
+        }
+        frontier = next;
+        depth += 1;
+    }
+    Ok(None)
+}
+
+/* Orders pages for a TieBreak policy, lower keys are preferred. Keys only depend on titles, link counts and scores,
+never on page ids, so the same dump always picks the same path no matter which order it was seeded in */
+struct Ranker<'a> {
+    graph: &'a Graph,
+    tie_break: TieBreak,
+    keys: RefCell<HashMap<usize, (u64, String)>>,
+}
+
+impl<'a> Ranker<'a> {
+    fn new(graph: &'a Graph, tie_break: TieBreak) -> Ranker<'a> {
+        Ranker {
+            graph,
+            tie_break,
+            keys: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn key(&self, id: usize) -> rusqlite::Result<(u64, String)> {
+        if let Some(key) = self.keys.borrow().get(&id) {
+            return Ok(key.clone());
+        }
+        let title = self.graph.page_title(id)?;
+        let rank = match self.tie_break {
+            TieBreak::None | TieBreak::Lexicographic => 0,
+            TieBreak::MostLinked => u64::MAX - self.graph.in_degree(id)? as u64,
+            TieBreak::LeastLinked => self.graph.in_degree(id)? as u64,
+            // Bit patterns of non negative floats sort the same way as the floats
+            TieBreak::Pagerank => u64::MAX - self.graph.pagerank(id)?.unwrap_or(0.0).to_bits(),
+            TieBreak::Random { seed } => str_utils::seeded_hash(&title, seed),
+        };
+        self.keys.borrow_mut().insert(id, (rank, title.clone()));
+        Ok((rank, title))
+    }
+}
+
+/* Sorts the parents of every page on a shortest path to end_id by rank, so that paths are numbered in rank order.
+Following the first parent of every page then gives the best ranked path, for single and all paths alike */
+fn rank_parents(
+    parents: &mut HashMap<usize, Vec<Parent>>,
+    end_id: usize,
+    ranker: &Ranker,
+) -> rusqlite::Result<()> {
+    let mut stack = vec![end_id];
+    let mut seen = HashSet::from([end_id]);
+    while let Some(id) = stack.pop() {
+        let mut keyed = Vec::new();
+        for parent in parents.get_mut(&id).unwrap().drain(..) {
+            keyed.push((ranker.key(parent.id)?, parent));
+        }
+        keyed.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, parent) in &keyed {
+            if seen.insert(parent.id) {
+                stack.push(parent.id);
+            }
+        }
+        parents.insert(id, keyed.into_iter().map(|(_, parent)| parent).collect());
+    }
+    Ok(())
+}
+
+// Compiled form of SearchOptions::avoid and avoid_disambiguation
+struct AvoidList {
+    ids: HashSet<usize>,
+    patterns: Vec<Regex>,
+    disambiguation: bool,
+}
+
+impl AvoidList {
+    fn new(graph: &Graph, avoid: &[String], disambiguation: bool) -> rusqlite::Result<AvoidList> {
+        let mut ids = HashSet::new();
+        let mut patterns = Vec::new();
+        for entry in avoid {
+            match avoid_pattern(entry) {
+                Some(pattern) => patterns.push(
+                    Regex::new(pattern).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?,
+                ),
+                None => ids.extend(graph.resolve(entry)?.map(|(id, _)| id)),
+            }
+        }
+        Ok(AvoidList {
+            ids,
+            patterns,
+            disambiguation,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ids.is_empty() && self.patterns.is_empty() && !self.disambiguation
+    }
+
+    fn contains(&self, link: &Link) -> bool {
+        (self.disambiguation && link.disambiguation)
+            || self.ids.contains(&link.id)
+            || self.patterns.iter().any(|pattern| pattern.is_match(&link.title))
+    }
+}
+
+/* A constrained search is split into segments, one between each pair of consecutive waypoints (start page, via
+pages, end page). Each segment is searched on its own with whatever is left of max_depth after the segments
+before it, avoided pages and pages outside the category filter are never used except as waypoints */
+struct Segments<'a> {
+    graph: &'a Graph,
+    waypoints: Vec<usize>,
+    avoid: AvoidList,
+    category: Option<(CategoryFilter, HashSet<usize>)>,
+    max_depth: Option<usize>,
+    edge_types: EdgeTypes,
+    ranker: Ranker<'a>,
+}
+
+// Search tree of one segment between two waypoints
+struct Segment {
+    end_id: usize,
+    parents: HashMap<usize, Vec<Parent>>,
+}
+
+impl<'a> Segments<'a> {
+    fn new(
+        graph: &'a Graph,
+        start_page: &str,
+        end_page: &str,
+        options: &SearchOptions,
+    ) -> rusqlite::Result<Outcome<Segments<'a>>> {
+        let mut waypoints = Vec::new();
+        let titles = std::iter::once(start_page).chain(options.via.iter().map(String::as_str)).chain([end_page]);
+        for title in titles {
+            match graph.resolve(title)? {
+                Some((id, _)) => waypoints.push(id),
+                None => {
+                    return Ok(Err(Unreachable::PageNotFound {
+                        title: title.to_string(),
+                    }));
+                }
+            }
+        }
+        let category = match &options.category {
+            Some(filter) => match graph.category_pages(filter)? {
+                Some(pages) => Some((filter.clone(), pages)),
+                None => {
+                    return Ok(Err(Unreachable::CategoryNotFound {
+                        category: filter.category.clone(),
+                    }));
+                }
+            },
+            None => None,
+        };
+        Ok(Ok(Segments {
+            graph,
+            waypoints,
+            avoid: AvoidList::new(graph, &options.avoid, options.avoid_disambiguation)?,
+            category,
+            max_depth: options.max_depth,
+            edge_types: options.edge_types,
+            ranker: Ranker::new(graph, options.tie_break),
+        }))
+    }
+
+    // Picking between paths of the same length needs every parent at the shortest distance
+    fn needs_all_parents(&self) -> bool {
+        self.ranker.tie_break != TieBreak::None
+    }
+
+    fn allowed(&self, link: &Link) -> bool {
+        self.edge_types.contains(link.edge_type) && self.passable(link)
+    }
+
+    // Whether a search can go through the page a link leads to, whatever the type of the link
+    fn passable(&self, link: &Link) -> bool {
+        self.waypoints.contains(&link.id)
+            || (!self.avoid.contains(link) && self.category.as_ref().is_none_or(|(_, pages)| pages.contains(&link.id)))
+    }
+
+    /* Searches every segment in order, with all_parents every shortest path through a segment is kept. Parents are
+    sorted by the tie break policy */
+    fn search(
+        &self,
+        all_parents: bool,
+        on_progress: &mut dyn FnMut(&SearchProgress) -> bool,
+    ) -> rusqlite::Result<Outcome<Vec<Segment>>> {
+        let all_parents = all_parents || self.needs_all_parents();
+        let mut segments = Vec::new();
+        let mut depth = 0;
+        for pair in self.waypoints.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            if self.graph.unreachable_by_components(from, to)? {
+                return Ok(Err(self.unconstrained_reason(from, to)?));
+            }
+            let max_depth = self.max_depth.map(|max_depth| max_depth - depth);
+            let allowed = |_: usize, link: &Link| self.allowed(link);
+            let Some(mut parents) =
+                bfs(self.graph, from, to, all_parents, &allowed, max_depth, on_progress)?
+            else {
+                return Ok(Err(self.diagnose(from, to)?));
+            };
+            depth += first_parent_path(&parents, to).len() - 1;
+            if self.needs_all_parents() {
+                rank_parents(&mut parents, to, &self.ranker)?;
+            }
+            segments.push(Segment {
+                end_id: to,
+                parents,
+            });
+        }
+        Ok(Ok(segments))
+    }
+
+    /* Works out which constraint stopped the segment from `from` to `to` by searching again with the constraints
+    loosened one at a time. Only runs once a search has already failed */
+    fn diagnose(&self, from: usize, to: usize) -> rusqlite::Result<Unreachable> {
+        let allowed = |_: usize, link: &Link| self.allowed(link);
+        if let Some(max_depth) = self.max_depth {
+            if bfs(self.graph, from, to, false, &allowed, None, &mut |_| true)?.is_some() {
+                return Ok(Unreachable::MaxDepth { max_depth });
+            }
+        }
+        let any_edge_type = |_: usize, link: &Link| self.passable(link);
+        if self.edge_types != EdgeTypes::ALL
+            && bfs(self.graph, from, to, false, &any_edge_type, None, &mut |_| true)?.is_some()
+        {
+            return Ok(Unreachable::EdgeType);
+        }
+        let any_category = |_: usize, link: &Link| !self.avoid.contains(link) || self.waypoints.contains(&link.id);
+        if let Some((filter, pages)) = &self.category {
+            if bfs(self.graph, from, to, false, &any_category, None, &mut |_| true)?.is_some() {
+                return Ok(Unreachable::Category {
+                    category: filter.category.clone(),
+                    pages: pages.len(),
+                });
+            }
+        }
+        if !self.avoid.is_empty()
+            && bfs(self.graph, from, to, false, &|_, _| true, None, &mut |_| true)?.is_some()
+        {
+            return Ok(Unreachable::Avoid);
+        }
+        self.unconstrained_reason(from, to)
+    }
+
+    // Reason given when there's no path from `from` to `to` even without constraints
+    fn unconstrained_reason(&self, from: usize, to: usize) -> rusqlite::Result<Unreachable> {
+        if self.waypoints.len() > 2 {
+            return Ok(Unreachable::Via {
+                from: self.graph.page_title(from)?,
+                to: self.graph.page_title(to)?,
+            });
+        }
+        Ok(Unreachable::None)
+    }
+}
+
+// Joins the paths of consecutive segments, each one starts at the page the previous one ended at
+fn join_segments(paths: Vec<Vec<Step>>) -> Vec<Step> {
+    let mut joined: Vec<Step> = Vec::new();
+    for path in paths {
+        let skip = if joined.is_empty() { 0 } else { 1 };
+        joined.extend(path.into_iter().skip(skip));
+    }
+    joined
+}
+
+/* Shortest path search is a breadth first search over the links of each page starting at start_page. Links that
+don't match a page are looked up in REDIRECTS, the page they redirect to is used instead and the redirect is
+noted in the path. on_progress is called every time the search moves to a new depth and at least every
+PROGRESS_INTERVAL while a depth is being expanded, it's what the /path/stream endpoint uses to report progress.
+When on_progress returns false the search stops with the error from search_cancelled.
+With via pages the path is made of the shortest path between each pair of consecutive waypoints */
+pub fn find_shortest_path(
+    db_path: &str,
+    start_page: &str,
+    end_page: &str,
+    options: &SearchOptions,
+    on_progress: &mut dyn FnMut(&SearchProgress) -> bool,
+) -> rusqlite::Result<Outcome<FoundPath>> {
+    find_shortest_path_in(&Graph::open(db_path)?, start_page, end_page, options, on_progress)
+}
+
+// Same as find_shortest_path on a graph that's already open, so many searches can share one connection
+pub fn find_shortest_path_in(
+    graph: &Graph,
+    start_page: &str,
+    end_page: &str,
+    options: &SearchOptions,
+    on_progress: &mut dyn FnMut(&SearchProgress) -> bool,
+) -> rusqlite::Result<Outcome<FoundPath>> {
+    let search_start = Instant::now();
+    let segments = match Segments::new(graph, start_page, end_page, options)? {
+        Ok(segments) => segments,
+        Err(reason) => return Ok(Err(reason)),
+    };
+
+    let found = match segments.search(false, on_progress)? {
+        Ok(found) => found,
+        Err(reason) => {
+            eprintln!("No path found in {:?}: {:?}", search_start.elapsed(), reason);
+            return Ok(Err(reason));
+        }
+    };
+    let steps = join_segments(
+        found
+            .iter()
+            .map(|segment| first_parent_path(&segment.parents, segment.end_id))
+            .collect(),
+    );
+    let path = format_path(graph, &steps)?;
+
+    eprintln!("{:?}", path.pages);
+    eprintln!("Path found in {:?}", search_start.elapsed());
+    Ok(Ok(path))
+}
+
+// Number of shortest paths from the start of the search to id, memoized in counts
+fn count_paths(id: usize, parents: &HashMap<usize, Vec<Parent>>, counts: &mut HashMap<usize, u64>) -> u64 {
+    if let Some(&count) = counts.get(&id) {
+        return count;
+    }
+    let count = if parents[&id].is_empty() {
+        1
+    } else {
+        parents[&id]
+            .iter()
+            .fold(0u64, |total, parent| total.saturating_add(count_paths(parent.id, parents, counts)))
+    };
+    counts.insert(id, count);
+    count
+}
+
+/* Builds shortest path number index to end_id. Paths are numbered by walking back from end_id through parents in
+the order they were discovered, picking the parent whose range of path numbers contains index */
+fn nth_path(
+    parents: &HashMap<usize, Vec<Parent>>,
+    counts: &HashMap<usize, u64>,
+    end_id: usize,
+    mut index: u64,
+) -> Vec<Step> {
+    let mut steps: VecDeque<Step> = VecDeque::new();
+    let mut cur = end_id;
+    loop {
+        let parent = parents[&cur].iter().find(|parent| {
+            let count = counts[&parent.id];
+            if index < count {
+                true
+            } else {
+                index -= count;
+                false
+            }
+        });
+        let Some(parent) = parent else {
+            steps.push_front(start_step(cur));
+            break;
+        };
+        steps.push_front(Step {
+            id: cur,
+            redirect: parent.redirect.clone(),
+            edge_type: parent.edge_type,
+        });
+        cur = parent.id;
+    }
+    steps.into()
+}
+
+/* Same search as find_shortest_path but keeps every parent at the shortest distance, so that all of the shortest
+paths between the two pages can be counted. Only paths offset..offset + limit are built. With via pages every
+combination of shortest paths between consecutive waypoints is a path, the last segment changing fastest */
+pub fn find_all_shortest_paths(
+    db_path: &str,
+    start_page: &str,
+    end_page: &str,
+    offset: usize,
+    limit: usize,
+    options: &SearchOptions,
+    on_progress: &mut dyn FnMut(&SearchProgress) -> bool,
+) -> rusqlite::Result<Outcome<AllShortestPaths>> {
+    let search_start = Instant::now();
+    let graph = Graph::open(db_path)?;
+    let segments = match Segments::new(&graph, start_page, end_page, options)? {
+        Ok(segments) => segments,
+        Err(reason) => return Ok(Err(reason)),
+    };
+
+    let found = match segments.search(true, on_progress)? {
+        Ok(found) => found,
+        Err(reason) => {
+            eprintln!("No path found in {:?}: {:?}", search_start.elapsed(), reason);
+            return Ok(Err(reason));
+        }
+    };
+    let mut counts: Vec<HashMap<usize, u64>> = Vec::new();
+    let mut segment_totals: Vec<u64> = Vec::new();
+    for segment in &found {
+        let mut segment_counts = HashMap::new();
+        segment_totals.push(count_paths(segment.end_id, &segment.parents, &mut segment_counts));
+        counts.push(segment_counts);
+    }
+    let total_paths = segment_totals
+        .iter()
+        .fold(1u64, |total, &count| total.saturating_mul(count));
+
+    let mut paths: Vec<FoundPath> = Vec::new();
+    let last = (offset as u64).saturating_add(limit as u64).min(total_paths);
+    for index in offset as u64..last {
+        let mut remaining = index;
+        let mut segment_paths: VecDeque<Vec<Step>> = VecDeque::new();
+        for (i, segment) in found.iter().enumerate().rev() {
+            let segment_index = remaining % segment_totals[i];
+            remaining /= segment_totals[i];
+            segment_paths.push_front(nth_path(&segment.parents, &counts[i], segment.end_id, segment_index));
+        }
+        paths.push(format_path(&graph, &join_segments(segment_paths.into()))?);
+    }
+
+    eprintln!(
+        "Found {} shortest paths in {:?}",
+        total_paths,
+        search_start.elapsed()
+    );
+    Ok(Ok(AllShortestPaths {
+        total_paths,
+        offset,
+        limit,
+        paths,
+    }))
+}
+
+/* Yen's algorithm for the k shortest loopless paths from start_page to end_page. Starting from the shortest path,
+every page of the last path found is used as a spur, a search from the spur to end_page that can't reuse the pages
+before it or any link already taken from the same root path. The shortest of those candidates becomes the next
+path. Paths are returned shortest first, there are fewer than k if that's all there is. avoid and max_depth apply
+to every path, via isn't supported since a path through waypoints doesn't have to be loopless */
+pub fn k_shortest_paths(
+    db_path: &str,
+    start_page: &str,
+    end_page: &str,
+    k: usize,
+    options: &SearchOptions,
+    on_progress: &mut dyn FnMut(&SearchProgress) -> bool,
+) -> rusqlite::Result<Outcome<Vec<FoundPath>>> {
+    let search_start = Instant::now();
+    if !options.via.is_empty() {
+        return Err(rusqlite::Error::InvalidParameterName(String::from("via")));
+    }
+    let graph = Graph::open(db_path)?;
+    let segments = match Segments::new(&graph, start_page, end_page, options)? {
+        Ok(segments) => segments,
+        Err(reason) => return Ok(Err(reason)),
+    };
+    let end_id = segments.waypoints[1];
+
+    let first = match segments.search(false, on_progress)? {
+        Ok(mut found) => found.remove(0),
+        Err(reason) => {
+            eprintln!("No path found in {:?}: {:?}", search_start.elapsed(), reason);
+            return Ok(Err(reason));
+        }
+    };
+    let mut found: Vec<Vec<Step>> = vec![first_parent_path(&first.parents, end_id)];
+    // Candidates are ordered by length, then by the order they were found in
+    let mut candidates: BinaryHeap<Reverse<(usize, usize)>> = BinaryHeap::new();
+    let mut candidate_paths: Vec<Vec<Step>> = Vec::new();
+    let mut known: HashSet<Vec<usize>> = HashSet::from([step_ids(&found[0])]);
+
+    while found.len() < k {
+        let prev = found.last().unwrap();
+        for i in 0..prev.len() - 1 {
+            let root = &prev[..=i];
+            let root_ids = step_ids(root);
+            let blocked_pages: HashSet<usize> = root_ids[..i].iter().copied().collect();
+            let blocked_links: HashSet<(usize, usize)> = found
+                .iter()
+                .filter(|path| path.len() > i + 1 && step_ids(&path[..=i]) == root_ids)
+                .map(|path| (path[i].id, path[i + 1].id))
+                .collect();
+            let allowed = |from: usize, link: &Link| {
+                segments.allowed(link)
+                    && !blocked_pages.contains(&link.id)
+                    && !blocked_links.contains(&(from, link.id))
+            };
+            let max_depth = options.max_depth.map(|max_depth| max_depth - i);
+            let all_parents = segments.needs_all_parents();
+            let Some(mut spur_parents) =
+                bfs(&graph, root[i].id, end_id, all_parents, &allowed, max_depth, &mut |_| true)?
+            else {
+                continue;
+            };
+            if all_parents {
+                rank_parents(&mut spur_parents, end_id, &segments.ranker)?;
+            }
+            let mut candidate = root.to_vec();
+            candidate.extend(first_parent_path(&spur_parents, end_id).into_iter().skip(1));
+            if known.insert(step_ids(&candidate)) {
+                candidates.push(Reverse((candidate.len(), candidate_paths.len())));
+                candidate_paths.push(candidate);
+            }
+        }
+        let Some(Reverse((_, next))) = candidates.pop() else {
+            break;
+        };
+        found.push(std::mem::take(&mut candidate_paths[next]));
+    }
+
+    let paths = found
+        .iter()
+        .map(|steps| format_path(&graph, steps))
+        .collect::<rusqlite::Result<Vec<FoundPath>>>()?;
+    eprintln!("Found {} paths in {:?}", paths.len(), search_start.elapsed());
+    Ok(Ok(paths))
+}
+
+fn step_ids(steps: &[Step]) -> Vec<usize> {
+    steps.iter().map(|step| step.id).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Database at a fresh path in the temp directory holding pages with the given links, removed when dropped
+    struct TestDb(String);
+
+    impl TestDb {
+        fn new(name: &str, links: &[(&str, &str)]) -> TestDb {
+            let path = std::env::temp_dir().join(format!("wikimapia-{}-{}.db", name, std::process::id()));
+            let path = path.to_string_lossy().into_owned();
+            let _ = std::fs::remove_file(&path);
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(include_str!("../create_tables.sql")).unwrap();
+            for &(from, to) in links {
+                for title in [from, to] {
+                    conn.execute("insert or ignore into PAGES(page_title) values (?1)", params![title])
+                        .unwrap();
+                }
+                conn.execute(
+                    "insert into LINKS(page_id, link_title) select id, ?2 from PAGES where page_title = ?1",
+                    params![from, to],
+                )
+                .unwrap();
+            }
+            TestDb(path)
+        }
+    }
+
+    impl Drop for TestDb {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn k_shortest_paths_are_loopless_and_shortest_first() {
+        let db = TestDb::new(
+            "k-shortest",
+            &[
+                ("A", "B"),
+                ("B", "A"),
+                ("B", "E"),
+                ("A", "C"),
+                ("C", "A"),
+                ("C", "E"),
+                ("C", "D"),
+                ("D", "E"),
+                ("A", "F"),
+                ("F", "G"),
+                ("G", "H"),
+                ("H", "E"),
+            ],
+        );
+        let paths = k_shortest_paths(&db.0, "A", "E", 10, &SearchOptions::default(), &mut |_| true)
+            .unwrap()
+            .unwrap();
+        let pages: Vec<Vec<&str>> = paths
+            .iter()
+            .map(|path| path.pages.iter().map(String::as_str).collect())
+            .collect();
+
+        // Only four paths don't go through a page twice, even though B and C link back to A
+        assert_eq!(pages.len(), 4);
+        let mut shortest = pages[..2].to_vec();
+        shortest.sort();
+        assert_eq!(shortest, vec![vec!["A", "B", "E"], vec!["A", "C", "E"]]);
+        assert_eq!(pages[2], vec!["A", "C", "D", "E"]);
+        assert_eq!(pages[3], vec!["A", "F", "G", "H", "E"]);
+        for path in &pages {
+            let distinct: HashSet<&str> = path.iter().copied().collect();
+            assert_eq!(distinct.len(), path.len(), "{:?} has a loop", path);
+        }
+    }
+
+    #[test]
+    fn k_shortest_paths_stops_at_k() {
+        let db = TestDb::new("k-limit", &[("A", "B"), ("B", "E"), ("A", "C"), ("C", "E"), ("A", "E")]);
+        let paths = k_shortest_paths(&db.0, "A", "E", 2, &SearchOptions::default(), &mut |_| true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].pages, vec!["A", "E"]);
+        assert_eq!(paths[1].pages.len(), 3);
+    }
+}
@@ -0,0 +1,377 @@
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::history::FileHistory;
+use rustyline::{Context, Editor, Helper, Highlighter, Hinter, Validator};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::depth::Direction;
+use crate::search::{EdgeType, EdgeTypes, SearchOptions};
+use crate::{
+    PATH_CACHE, batch, cached_single_path, check_for_page, cli, db_path, depth, page, seed_db, str_utils,
+};
+
+// Commands of the shell with what they do, in the order help lists them
+const COMMANDS: [(&str, &str); 10] = [
+    ("h", "Displays this message"),
+    ("reseed", "Re seeds database of connections"),
+    ("search", "Starts shortest path search between articles"),
+    ("depth", "Finds how far the articles reachable from an article are"),
+    ("reach", "Finds how far the articles that can reach an article are"),
+    ("backlinks", "Lists the articles linking to an article"),
+    ("find", "Searches article titles and descriptions"),
+    ("batch", "Finds shortest paths for pairs read from a csv or jsonl file"),
+    ("cache", "Displays path cache statistics"),
+    ("exit", "Exits application"),
+];
+
+// File in the home directory the history of the shell is kept in between runs
+const HISTORY_FILE: &str = ".wikimapia_history";
+
+// Most titles offered when completing a title with tab, and when asking which title was meant
+const MAX_COMPLETIONS: usize = 20;
+const MAX_DID_YOU_MEAN: usize = 5;
+
+// What the line being typed is, tab completes command names, article titles or file paths
+#[derive(Clone, Copy)]
+enum Input {
+    Command,
+    Title,
+    File,
+}
+
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct ShellHelper {
+    input: Input,
+    files: FilenameCompleter,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let typed = &line[..pos];
+        let candidates: Vec<String> = match self.input {
+            Input::Command => COMMANDS
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .filter(|name| name.starts_with(typed))
+                .collect(),
+            // Titles are completed from SUGGESTIONS, redirects complete to the page they point to
+            Input::Title => {
+                let mut titles: Vec<String> = page::suggest(db_path(), typed, MAX_COMPLETIONS)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|suggestion| suggestion.redirect_to.unwrap_or(suggestion.title))
+                    .collect();
+                let mut seen = std::collections::HashSet::new();
+                titles.retain(|title| seen.insert(title.clone()));
+                titles
+            }
+            Input::File => return self.files.complete(line, pos, ctx),
+        };
+        let pairs = candidates
+            .into_iter()
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: candidate,
+            })
+            .collect();
+        Ok((0, pairs))
+    }
+}
+
+struct Shell {
+    editor: Editor<ShellHelper, FileHistory>,
+}
+
+impl Shell {
+    // Reads a line of the given kind, lines are added to the history so they can be brought back with the arrow keys
+    fn readline(&mut self, prompt: &str, input: Input) -> rustyline::Result<String> {
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.input = input;
+        }
+        let line = self.editor.readline(prompt)?.trim().to_string();
+        if !line.is_empty() {
+            self.editor.add_history_entry(line.as_str())?;
+        }
+        Ok(line)
+    }
+
+    // Reads the answer to a prompt of a command, None if it was cancelled with ctrl-c or input ended
+    fn read(&mut self, prompt: &str, input: Input) -> rustyline::Result<Option<String>> {
+        match self.readline(prompt, input) {
+            Ok(line) => Ok(Some(line)),
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /* Reads the title of a page. A title that isn't a page is matched without case and by prefix against the titles
+    that are, and the user is asked whether they meant one of those. None if nothing was picked */
+    fn read_title(&mut self, prompt: &str) -> rustyline::Result<Option<String>> {
+        let Some(title) = self.read(prompt, Input::Title)? else {
+            return Ok(None);
+        };
+        let title = str_utils::normalize_title(&title);
+        if check_for_page(&title).is_ok() {
+            return Ok(Some(title));
+        }
+        let candidates = match page::did_you_mean(db_path(), &title, MAX_DID_YOU_MEAN) {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                eprintln!("Error looking for similar pages: {}", e);
+                Vec::new()
+            }
+        };
+        match candidates.as_slice() {
+            [] => {
+                eprintln!("Page {} doesn't exist", title);
+                Ok(None)
+            }
+            [candidate] => {
+                let prompt = format!("Page {} doesn't exist, did you mean {}? [Y/n] ", title, candidate);
+                let answer = self.read(&prompt, Input::Command)?;
+                match answer.as_deref().map(str::to_lowercase).as_deref() {
+                    Some("" | "y" | "yes") => Ok(Some(candidate.clone())),
+                    _ => Ok(None),
+                }
+            }
+            candidates => {
+                println!("Page {} doesn't exist, did you mean:", title);
+                for (i, candidate) in candidates.iter().enumerate() {
+                    println!("{:>3}  {}", i + 1, candidate);
+                }
+                let answer = self.read("Enter a number, blank for none: ", Input::Command)?;
+                match answer.and_then(|answer| answer.parse::<usize>().ok()) {
+                    Some(i) if (1..=candidates.len()).contains(&i) => Ok(Some(candidates[i - 1].clone())),
+                    _ => Ok(None),
+                }
+            }
+        }
+    }
+
+    // Asks which edge types to leave out, None if it was cancelled. Unknown types are reported and ignored
+    fn read_edge_types(&mut self) -> rustyline::Result<Option<EdgeTypes>> {
+        let Some(excluded) = self.read("Exclude edge types (comma separated, blank for none): ", Input::Command)? else {
+            return Ok(None);
+        };
+        let mut edge_types = EdgeTypes::ALL;
+        for name in excluded.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            match EdgeType::parse(name) {
+                Some(edge_type) => edge_types.remove(edge_type),
+                None => eprintln!("Unknown edge type {}, ignoring it", name),
+            }
+        }
+        Ok(Some(edge_types))
+    }
+
+    fn search(&mut self) -> rustyline::Result<()> {
+        let Some(start_page) = self.read_title("Enter start page: ")? else {
+            return Ok(());
+        };
+        let Some(end_page) = self.read_title("Enter end page: ")? else {
+            return Ok(());
+        };
+        match cached_single_path(&start_page, &end_page, &SearchOptions::default()) {
+            Ok(Ok(path)) => println!("{}", cli::path_line(&path)),
+            Ok(Err(reason)) => println!("No path found, {}", reason),
+            Err(e) => eprintln!("Error finding path: {}", e),
+        }
+        Ok(())
+    }
+
+    fn backlinks(&mut self) -> rustyline::Result<()> {
+        let Some(title) = self.read_title("Enter page: ")? else {
+            return Ok(());
+        };
+        match page::backlinks(db_path(), &title, 0, page::DEFAULT_BACKLINK_LIMIT) {
+            Ok(Some(backlinks)) => {
+                for backlink in &backlinks.backlinks {
+                    match &backlink.redirect {
+                        Some(redirect) => println!("{} (via {})", backlink.title, redirect),
+                        None => println!("{}", backlink.title),
+                    }
+                }
+                if backlinks.total > backlinks.backlinks.len() {
+                    println!("... and {} more", backlinks.total - backlinks.backlinks.len());
+                }
+                println!(
+                    "{} pages link to {}: {} directly, {} only through redirects",
+                    backlinks.total, backlinks.title, backlinks.direct, backlinks.via_redirect
+                );
+            }
+            Ok(None) => eprintln!("Page {} doesn't exist", title),
+            Err(e) => eprintln!("Error finding backlinks: {}", e),
+        }
+        Ok(())
+    }
+
+    fn find(&mut self) -> rustyline::Result<()> {
+        let Some(query) = self.read("Enter search: ", Input::Title)? else {
+            return Ok(());
+        };
+        match page::find(db_path(), &query, 0, page::DEFAULT_TEXT_SEARCH_LIMIT) {
+            Ok(Some(results)) => {
+                for result in &results {
+                    println!("{}: {}", result.title, result.snippet);
+                }
+                println!("{} results", results.len());
+            }
+            Ok(None) => eprintln!("The full text search index wasn't built, reseed to build it"),
+            Err(e) => eprintln!("Error searching: {}", e),
+        }
+        Ok(())
+    }
+
+    fn batch(&mut self) -> rustyline::Result<()> {
+        let Some(input_path) = self.read("Enter input file (.csv or .jsonl): ", Input::File)? else {
+            return Ok(());
+        };
+        let input = match std::fs::read_to_string(&input_path) {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("Couldn't read {}: {}", input_path, e);
+                return Ok(());
+            }
+        };
+        let queries = if input_path.ends_with(".csv") {
+            batch::parse_csv(&input)
+        } else {
+            batch::parse_jsonl(&input)
+        };
+        let queries = match queries {
+            Ok(queries) => queries,
+            Err(e) => {
+                eprintln!("Couldn't parse {}: {}", input_path, e);
+                return Ok(());
+            }
+        };
+        let Some(output_path) = self.read("Enter output file (.csv or .jsonl): ", Input::File)? else {
+            return Ok(());
+        };
+
+        let Some(edge_types) = self.read_edge_types()? else {
+            return Ok(());
+        };
+        let options = SearchOptions {
+            edge_types,
+            ..SearchOptions::default()
+        };
+
+        let batch_start = Instant::now();
+        let results = match batch::run_batch(db_path(), queries, &options) {
+            Ok(results) => results,
+            Err(e) => {
+                eprintln!("Error running batch: {}", e);
+                return Ok(());
+            }
+        };
+        let output = if output_path.ends_with(".csv") {
+            batch::to_csv(&results)
+        } else {
+            batch::to_jsonl(&results)
+        };
+        if let Err(e) = std::fs::write(&output_path, output) {
+            eprintln!("Couldn't write {}: {}", output_path, e);
+            return Ok(());
+        }
+        let found = results
+            .iter()
+            .filter(|result| matches!(result.status, batch::BatchStatus::Ok))
+            .count();
+        println!(
+            "Found paths for {}/{} pairs in {:?}, results written to {}",
+            found,
+            results.len(),
+            batch_start.elapsed(),
+            output_path
+        );
+        Ok(())
+    }
+
+    fn depth(&mut self, direction: Direction) -> rustyline::Result<()> {
+        let prompt = match direction {
+            Direction::Forward => "Enter start page: ",
+            Direction::Reverse => "Enter target page: ",
+        };
+        let Some(start_page) = self.read_title(prompt)? else {
+            return Ok(());
+        };
+        let Some(edge_types) = self.read_edge_types()? else {
+            return Ok(());
+        };
+
+        match depth::depth_report(db_path(), &start_page, direction, edge_types, None, depth::DEFAULT_FARTHEST_LIMIT) {
+            Ok(Ok(report)) => cli::print_depth_report(&report),
+            Ok(Err(reason)) => eprintln!("{}", str_utils::capitalize_first_char(&reason.to_string())),
+            Err(e) => eprintln!("Error finding depth: {}", e),
+        }
+        Ok(())
+    }
+}
+
+// History is kept in the home directory, or the working directory if there's no home
+fn history_path() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(HISTORY_FILE)
+}
+
+/* Interactive shell with line editing and a history kept between runs. Tab completes commands, article titles from
+the local index and file paths. Ctrl-c cancels the command being entered and ctrl-d exits */
+pub fn run() -> rustyline::Result<()> {
+    let mut editor = Editor::<ShellHelper, FileHistory>::new()?;
+    editor.set_helper(Some(ShellHelper {
+        input: Input::Command,
+        files: FilenameCompleter::new(),
+    }));
+    let history_path = history_path();
+    // There's no history the first time the shell is started
+    let _ = editor.load_history(&history_path);
+    let mut shell = Shell { editor };
+
+    println!("Wikimapia v0.2.0. Enter 'h' for list of commands");
+    loop {
+        let input = match shell.readline("> ", Input::Command) {
+            Ok(input) => input,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e),
+        };
+
+        match input.as_str() {
+            "h" => {
+                for (name, description) in COMMANDS {
+                    println!("{:<10} {}", name, description);
+                }
+            }
+            "reseed" => {
+                if let Err(e) = seed_db(false) {
+                    eprintln!("Error seeding database: {}", e);
+                }
+            }
+            "search" => shell.search()?,
+            "backlinks" => shell.backlinks()?,
+            "find" => shell.find()?,
+            "batch" => shell.batch()?,
+            "depth" => shell.depth(Direction::Forward)?,
+            "reach" => shell.depth(Direction::Reverse)?,
+            "cache" => {
+                let stats = PATH_CACHE.stats();
+                println!(
+                    "Entries: {}/{}, hits: {}, misses: {}",
+                    stats.entries, stats.capacity, stats.hits, stats.misses
+                );
+            }
+            "exit" => break,
+            "" => {}
+            _ => println!("Invalid input, enter 'h' for list of commands."),
+        }
+    }
+    if let Err(e) = shell.editor.save_history(&history_path) {
+        eprintln!("Couldn't save history to {}: {}", history_path.display(), e);
+    }
+    Ok(())
+}
@@ -6,8 +6,34 @@ lazy_static::lazy_static! {
         .case_insensitive(true)
         .build()
         .unwrap();
+    static ref SHORT_DESCRIPTION_REGEX: regex::Regex =
+        RegexBuilder::new(r"\{\{\s*short description\s*\|([^}|]*)")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+    // Comments and references, neither of them is part of the text of an article
+    static ref HIDDEN_MARKUP_REGEX: regex::Regex =
+        regex::Regex::new(r"(?s)<!--.*?-->|<ref[^>]*/>|<ref[^>]*>.*?</ref>").unwrap();
+    // [[target|text]] or [[target]], captures what's displayed
+    static ref WIKILINK_REGEX: regex::Regex =
+        regex::Regex::new(r"\[\[(?:[^\]|]*\|)?([^\]]*)\]\]").unwrap();
+    // [http://example.com text], captures what's displayed
+    static ref EXTERNAL_LINK_REGEX: regex::Regex =
+        regex::Regex::new(r"\[https?://\S*\s*([^\]]*)\]").unwrap();
+    static ref HTML_TAG_REGEX: regex::Regex = regex::Regex::new(r"<[^>]*>").unwrap();
+    /* {{Disambiguation}} and the templates of its family, the short forms and every "... disambiguation" template
+    like {{Human name disambiguation}} or {{Place name disambiguation}} */
+    static ref DISAMBIGUATION_TEMPLATE_REGEX: regex::Regex = RegexBuilder::new(
+        r"\{\{\s*(disambiguation|disambig|disamb|dab|hndis|geodis|numberdis|[^{}|]*[ _]disambiguation)\s*(\||\}\})"
+    )
+    .case_insensitive(true)
+    .build()
+    .unwrap();
 }
 
+// Longest description kept for a page, longer ones are cut off
+const MAX_DESCRIPTION_LEN: usize = 300;
+
 // Simple function that takes a string and returns the same string with the first letter capitalized
 #[inline]
 pub fn capitalize_first_char(s: &str) -> String {
@@ -20,17 +46,145 @@ pub fn capitalize_first_char(s: &str) -> String {
 
 // Checks strings against strings to make sure they don't link to namespace pages
 #[inline]
-pub fn process_article_name<'a>(name: &'a str) -> Option<&'a str> {
+pub fn process_article_name(name: &str) -> Option<&str> {
     if name.starts_with(":") {
         return None;
     }
 
     let mut split = name.split("|");
     if let Some(processed_name) = split.next() {
-        if NAMESPACE_REGEX.is_match(&(processed_name.split(" ").next().unwrap())) {
+        if NAMESPACE_REGEX.is_match(processed_name.split(" ").next().unwrap()) {
             return None;
         }
-        return Some(&processed_name);
+        return Some(processed_name);
+    }
+    None
+}
+
+// Normalizes a title the way Wikipedia does, underscores become spaces, repeated whitespace is collapsed
+// and the first letter is capitalized. "albert_einstein " and "Albert Einstein" refer to the same page
+pub fn normalize_title(title: &str) -> String {
+    let spaced = title.replace('_', " ");
+    let collapsed = spaced.split_whitespace().collect::<Vec<&str>>().join(" ");
+    capitalize_first_char(&collapsed)
+}
+
+/* Hashes a string together with a seed. Unlike the hashers in std this is guaranteed to give the same value on every
+run and every Rust version, which seeded random tie breaking relies on (FNV-1a mixed with splitmix64) */
+pub fn seeded_hash(s: &str, seed: u64) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in s.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    let mut z = hash ^ seed.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/* Name of the category a [[Category:Name|sort key]] link puts a page in, without the prefix. None for every other
+link, including [[:Category:Name]] which links to the category page instead */
+pub fn category_name(link: &str) -> Option<String> {
+    let (namespace, rest) = link.split_once(':')?;
+    if !namespace.trim().eq_ignore_ascii_case("category") {
+        return None;
+    }
+    let name = normalize_title(rest.split('|').next().unwrap_or_default());
+    if name.is_empty() { None } else { Some(name) }
+}
+
+// Whether a page is a disambiguation page, from its title and wikitext
+pub fn is_disambiguation(title: &str, text: &str) -> bool {
+    title.ends_with("(disambiguation)") || DISAMBIGUATION_TEMPLATE_REGEX.is_match(text)
+}
+
+/* Short description of an article from its wikitext. The {{Short description|...}} template is used when the
+article has one, otherwise the first sentence of its lead section with the markup removed */
+pub fn extract_description(text: &str) -> Option<String> {
+    if let Some(cap) = SHORT_DESCRIPTION_REGEX.captures(text) {
+        let description = cap[1].trim();
+        if !description.is_empty() && !description.eq_ignore_ascii_case("none") {
+            return Some(truncate(description, MAX_DESCRIPTION_LEN));
+        }
+    }
+    let text = HIDDEN_MARKUP_REGEX.replace_all(text, "");
+    let text = strip_templates(&text);
+    // The lead is the first paragraph that isn't a heading, list, table row or lone image
+    let lead = text.lines().map(str::trim).find(|line| {
+        !line.is_empty()
+            && !line.starts_with(['=', '|', '!', '*', '#', ':', ';', '_'])
+            && !line.starts_with("[[File:")
+            && !line.starts_with("[[Image:")
+            && !line.starts_with("[[Category:")
+    })?;
+    let lead = WIKILINK_REGEX.replace_all(lead, "$1");
+    let lead = EXTERNAL_LINK_REGEX.replace_all(&lead, "$1");
+    let lead = HTML_TAG_REGEX.replace_all(&lead, "");
+    let lead = lead.replace("'''", "").replace("''", "");
+    let lead = lead.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let sentence = first_sentence(&lead);
+    if sentence.is_empty() {
+        None
+    } else {
+        Some(truncate(sentence, MAX_DESCRIPTION_LEN))
+    }
+}
+
+/* Removes {{templates}} and {| tables |}, both can be nested. Each one only ends with its own closing braces, so
+the |} of a template with an empty last parameter like {{foo|}} isn't taken for the end of a table */
+fn strip_templates(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut closers: Vec<&str> = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("{{") {
+            closers.push("}}");
+            rest = &rest[2..];
+        } else if rest.starts_with("{|") {
+            closers.push("|}");
+            rest = &rest[2..];
+        } else if closers.last().is_some_and(|closer| rest.starts_with(closer)) {
+            closers.pop();
+            rest = &rest[2..];
+        } else {
+            if closers.is_empty() {
+                stripped.push(c);
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    stripped
+}
+
+/* Text up to the first full stop followed by a space and a capital letter, or all of it if there's none. Short
+capitalized words like "St." and "Dr." and initials like "U.S." are taken to be abbreviations, not the end of a
+sentence */
+fn first_sentence(text: &str) -> &str {
+    for (i, _) in text.match_indices(". ") {
+        let word = text[..i].rsplit(' ').next().unwrap_or_default();
+        let abbreviation =
+            word.contains('.') || (word.chars().count() <= 3 && word.starts_with(char::is_uppercase));
+        if !abbreviation && text[i + 2..].starts_with(char::is_uppercase) {
+            return &text[..=i];
+        }
+    }
+    text
+}
+
+// Cuts a string down to at most max_len characters
+fn truncate(s: &str, max_len: usize) -> String {
+    match s.char_indices().nth(max_len) {
+        Some((end, _)) => format!("{}...", s[..end].trim_end()),
+        None => s.to_string(),
+    }
+}
+
+// Quotes a csv field if it contains a comma, quote or newline, quotes inside it are doubled
+pub fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
     }
-    return None;
 }